@@ -0,0 +1,23 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // Squash only searches its built-in plugin directory when SQUASH_PLUGINS is unset, and
+    // doesn't expose it, so it is looked up here for `Context::set_search_path`
+    if cfg!(feature = "docs-rs") {
+        return;
+    }
+    if let Some(directory) = plugin_directory() {
+        println!("cargo:rustc-env=SQUASH_BUILTIN_PLUGIN_DIR={}", directory);
+    }
+}
+
+fn plugin_directory() -> Option<String> {
+    let variable = |name| {
+        pkg_config::get_variable("squash-0.8", name)
+            .ok()
+            .filter(|value| !value.is_empty())
+    };
+    // Squash installs its plugins under its library directory unless configured otherwise
+    variable("plugindir")
+        .or_else(|| variable("libdir").map(|libdir| format!("{}/squash/0.8/plugins", libdir)))
+}
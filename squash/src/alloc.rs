@@ -0,0 +1,653 @@
+//! Control over how squash allocates memory
+//!
+//! By default squash and its plugins allocate with the C library's `malloc`. Calling
+//! [`use_rust_allocator`] routes all of their allocations through Rust's
+//! [global allocator](std::alloc::GlobalAlloc) instead, so an allocator like jemalloc or
+//! mimalloc sees them too.
+//!
+//! [`use_tracking_allocator`] does the same, and also keeps count of squash's allocations, so
+//! [`measure`] can report how much memory an operation needed.
+//!
+//! Once either is installed, squash's memory use can be capped, for the whole process with
+//! [`set_memory_limit`] or for an operation with [`with_memory_limit`]. Allocations beyond the
+//! limit fail, and the operation returns a `SQUASH_MEMORY` [`Error`](crate::Error) recording
+//! the [limit which was exceeded](MemoryLimitExceeded).
+
+use std::alloc::{self as rust_alloc, Layout};
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::{error, fmt, mem, ptr};
+
+use squash_sys::*;
+
+use crate::testing::Fault;
+
+/// The alignment of plain allocations, like `malloc`'s: suitable for any type
+const MIN_ALIGN: usize = 16;
+/// Space reserved before each allocation for its header, keeping it aligned to `MIN_ALIGN`
+const HEADER_SPACE: usize = 32;
+/// Written at the start of each header, to catch pointers which weren't allocated by us
+const CANARY: u64 = 0xBADC_0FFE_E0DD_F00D;
+
+/// Recorded just before each allocation, so it can be freed or resized without knowing its
+/// layout
+#[derive(Copy, Clone)]
+struct Header {
+    canary: u64,
+    size: usize,
+    align: usize,
+}
+
+const RUST_MEMORY_FUNCS: SquashMemoryFuncs = SquashMemoryFuncs {
+    malloc: Some(rust_malloc),
+    realloc: Some(rust_realloc),
+    calloc: Some(rust_calloc),
+    free: Some(rust_free),
+    aligned_alloc: Some(rust_aligned_alloc),
+    aligned_free: Some(rust_free),
+};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// The process-wide limit, or `usize::MAX` for none
+static GLOBAL_LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// The number of bytes squash currently has allocated
+static GLOBAL_LIVE: AtomicUsize = AtomicUsize::new(0);
+/// The number of blocks squash currently has allocated
+static GLOBAL_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+/// The number of pointers passed to `free` or `realloc` without a valid header
+static CANARY_FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The statistics of the innermost call to `measure` on this thread, if any
+    static SCOPE: Cell<Option<MemoryStats>> = const { Cell::new(None) };
+    /// The budget of the innermost call to `with_memory_limit` on this thread, if any
+    static BUDGET: Cell<Option<Budget>> = const { Cell::new(None) };
+    /// The last allocation refused by a limit on this thread, until picked up by an error
+    static LIMIT_FAILURE: Cell<Option<MemoryLimitExceeded>> = const { Cell::new(None) };
+    /// Whether an allocation failed on this thread since the last error was picked up
+    static ALLOCATION_FAILED: Cell<bool> = const { Cell::new(false) };
+    /// The faults being injected into allocations on this thread, if any
+    static FAULTS: Cell<Option<FaultState>> = const { Cell::new(None) };
+}
+
+/// The error returned when squash's memory functions have already been replaced
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AlreadyInstalled;
+
+impl fmt::Display for AlreadyInstalled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("squash's memory functions have already been replaced")
+    }
+}
+
+impl error::Error for AlreadyInstalled {}
+
+/// Route all of squash's allocations through Rust's global allocator
+///
+/// This replaces `malloc`, `calloc`, `realloc`, `free`, `aligned_alloc` and `aligned_free`
+/// with `squash_set_memory_functions`. It can only be done once: later calls return
+/// [`AlreadyInstalled`].
+///
+/// Each block starts with a canary value, checked when it is freed or reallocated. A pointer
+/// without it wasn't allocated by this allocator, so rather than passing it to Rust's
+/// allocator with a bogus layout, `free` leaks it and `realloc` fails, returning null.
+///
+/// # Safety
+/// This must be called before squash allocates anything, i.e. before any codec, stream,
+/// options or file is used, ideally at the start of `main`. Memory squash allocated before
+/// would be freed through the wrong allocator.
+pub unsafe fn use_rust_allocator() -> Result<(), AlreadyInstalled> {
+    install(RUST_MEMORY_FUNCS)
+}
+
+/// Route all of squash's allocations through Rust's global allocator, keeping count of them
+/// for [`measure`]
+///
+/// Like [`use_rust_allocator`], this can only be done once, and only one of them can be used.
+/// Invalid pointers are handled the same way, and also reported on stderr.
+///
+/// # Safety
+/// As for [`use_rust_allocator`], this must be called before squash allocates anything.
+pub unsafe fn use_tracking_allocator() -> Result<(), AlreadyInstalled> {
+    install(RUST_MEMORY_FUNCS)?;
+    TRACKING.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Memory allocated by squash during an operation measured by [`measure`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct MemoryStats {
+    /// Bytes allocated during the operation and not freed by the end of it
+    pub live: usize,
+    /// The most bytes allocated at once during the operation
+    pub peak: usize,
+    /// The total number of bytes allocated, including reallocations
+    pub allocated: u64,
+    /// The number of allocations
+    pub allocations: u64,
+    /// The number of reallocations
+    pub reallocations: u64,
+    /// The number of deallocations
+    pub deallocations: u64,
+}
+
+impl MemoryStats {
+    fn grow(&mut self, size: usize) {
+        self.live = self.live.saturating_add(size);
+        self.peak = self.peak.max(self.live);
+        self.allocated += size as u64;
+    }
+
+    fn shrink(&mut self, size: usize) {
+        // Memory allocated before the operation may be freed during it
+        self.live = self.live.saturating_sub(size);
+    }
+
+    /// Fold in the statistics of an operation nested inside this one
+    fn nest(self, inner: MemoryStats) -> MemoryStats {
+        MemoryStats {
+            live: self.live.saturating_add(inner.live),
+            peak: self.peak.max(self.live.saturating_add(inner.peak)),
+            allocated: self.allocated + inner.allocated,
+            allocations: self.allocations + inner.allocations,
+            reallocations: self.reallocations + inner.reallocations,
+            deallocations: self.deallocations + inner.deallocations,
+        }
+    }
+}
+
+/// Run `f`, measuring the memory squash allocates on this thread while it runs
+///
+/// Only allocations made by squash and its plugins are counted, and only once
+/// [`use_tracking_allocator`] has been called: otherwise the statistics are all zero. Memory
+/// is attributed to the thread allocating or freeing it, so work squash hands off to other
+/// threads isn't counted.
+///
+/// The first use of a codec loads its plugin, which allocates: use the codec once beforehand
+/// to measure only the operation itself. Calls may be nested, and the outer call's statistics
+/// include the inner call's.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// unsafe { squash::alloc::use_tracking_allocator()? };
+/// let codec = squash::Codec::find("zstd").unwrap();
+/// let (compressed, stats) = squash::alloc::measure(|| codec.compress(b"data", None));
+/// println!("peak: {} bytes in {} allocations", stats.peak, stats.allocations);
+/// # compressed?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn measure<T, F: FnOnce() -> T>(f: F) -> (T, MemoryStats) {
+    let scope = Scope::enter();
+    let result = f();
+    (result, scope.exit())
+}
+
+/// Restores the enclosing scope on exit, even if the measured function panics
+struct Scope {
+    outer: Option<MemoryStats>,
+}
+
+impl Scope {
+    fn enter() -> Self {
+        Scope {
+            outer: SCOPE.with(|scope| scope.replace(Some(MemoryStats::default()))),
+        }
+    }
+
+    fn exit(self) -> MemoryStats {
+        let stats = SCOPE.with(Cell::get).unwrap_or_default();
+        drop(self);
+        stats
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        SCOPE.with(|scope| {
+            let inner = scope.take().unwrap_or_default();
+            scope.set(self.outer.map(|outer| outer.nest(inner)));
+        });
+    }
+}
+
+/// Update the statistics of the current `measure` call, if tracking
+fn record<F: FnOnce(&mut MemoryStats)>(update: F) {
+    if !TRACKING.load(Ordering::Relaxed) {
+        return;
+    }
+    // Thread locals may already be gone while a thread exits
+    let _ = SCOPE.try_with(|scope| {
+        if let Some(mut stats) = scope.get() {
+            update(&mut stats);
+            scope.set(Some(stats));
+        }
+    });
+}
+
+/// An allocation refused because it would have taken squash over a memory limit
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MemoryLimitExceeded {
+    /// The limit, in bytes
+    pub limit: usize,
+    /// The size of the refused allocation, in bytes
+    pub requested: usize,
+}
+
+impl fmt::Display for MemoryLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "allocating {} bytes would exceed the memory limit of {} bytes",
+            self.requested, self.limit
+        )
+    }
+}
+
+impl error::Error for MemoryLimitExceeded {}
+
+/// Limit the memory squash may have allocated at once, across all threads
+///
+/// Pass `None` to remove the limit. Lowering the limit below the memory already allocated
+/// doesn't free anything, but makes further allocations fail. This has no effect unless
+/// [`use_rust_allocator`] or [`use_tracking_allocator`] was called.
+pub fn set_memory_limit(limit: Option<usize>) {
+    GLOBAL_LIMIT.store(limit.unwrap_or(usize::MAX), Ordering::SeqCst);
+}
+
+/// The limit set by [`set_memory_limit`], if any
+pub fn memory_limit() -> Option<usize> {
+    match GLOBAL_LIMIT.load(Ordering::SeqCst) {
+        usize::MAX => None,
+        limit => Some(limit),
+    }
+}
+
+/// The number of bytes squash currently has allocated, across all threads
+///
+/// This is always `0` unless [`use_rust_allocator`] or [`use_tracking_allocator`] was
+/// called.
+pub fn memory_in_use() -> usize {
+    GLOBAL_LIVE.load(Ordering::SeqCst)
+}
+
+/// Run `f`, limiting the memory squash may allocate on this thread while it runs to `limit`
+/// bytes
+///
+/// Only memory allocated during the call and not yet freed counts towards the limit. Calls
+/// may be nested, and the inner limit is capped by what remains of the outer one. As with
+/// [`set_memory_limit`], this has no effect unless squash's allocator was replaced.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// unsafe { squash::alloc::use_rust_allocator()? };
+/// let codec = squash::Codec::find("xz").unwrap();
+/// # let untrusted: &[u8] = &[];
+/// match squash::alloc::with_memory_limit(64 << 20, || codec.decompress(untrusted, None)) {
+///     Err(err) if err.memory_limit_exceeded().is_some() => eprintln!("input too large"),
+///     result => println!("{} bytes", result?.len()),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn with_memory_limit<T, F: FnOnce() -> T>(limit: usize, f: F) -> T {
+    let _budget = BudgetScope::enter(limit);
+    f()
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Budget {
+    limit: usize,
+    live: usize,
+}
+
+/// Restores the enclosing budget on exit, even if the limited function panics
+struct BudgetScope {
+    outer: Option<Budget>,
+}
+
+impl BudgetScope {
+    fn enter(limit: usize) -> Self {
+        BUDGET.with(|budget| {
+            let outer = budget.get();
+            let limit = match outer {
+                Some(outer) => limit.min(outer.limit.saturating_sub(outer.live)),
+                None => limit,
+            };
+            budget.set(Some(Budget { limit, live: 0 }));
+            BudgetScope { outer }
+        })
+    }
+}
+
+impl Drop for BudgetScope {
+    fn drop(&mut self) {
+        BUDGET.with(|budget| {
+            let inner = budget.get().map_or(0, |inner| inner.live);
+            budget.set(self.outer.map(|outer| Budget {
+                live: outer.live.saturating_add(inner),
+                ..outer
+            }));
+        });
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct FaultState {
+    fault: Fault,
+    rng: u64,
+    /// The number of allocations seen so far
+    pub(crate) allocations: u64,
+    /// The number of allocations failed so far
+    pub(crate) injected: u64,
+}
+
+impl FaultState {
+    fn should_fail(&mut self) -> bool {
+        let index = self.allocations;
+        self.allocations += 1;
+        let fail = match self.fault {
+            Fault::Nth(n) => index == n,
+            Fault::Random { probability, .. } => {
+                // xorshift64*: plenty for picking allocations to fail
+                self.rng ^= self.rng >> 12;
+                self.rng ^= self.rng << 25;
+                self.rng ^= self.rng >> 27;
+                let sample = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+                (sample as f64 / (1u64 << 53) as f64) < probability
+            }
+        };
+        self.injected += fail as u64;
+        fail
+    }
+}
+
+/// Run `f`, injecting `fault` into squash's allocations on this thread
+///
+/// Returns the final state of the fault injection, replacing any enclosing one while `f` runs.
+pub(crate) fn with_faults<T, F: FnOnce() -> T>(fault: Fault, f: F) -> (T, FaultState) {
+    struct Restore(Option<FaultState>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            FAULTS.with(|faults| faults.set(self.0));
+        }
+    }
+
+    let seed = match fault {
+        Fault::Random { seed, .. } => seed,
+        Fault::Nth(_) => 0,
+    };
+    let state = FaultState {
+        fault,
+        // xorshift gets stuck at zero
+        rng: if seed == 0 {
+            0x9E37_79B9_7F4A_7C15
+        } else {
+            seed
+        },
+        allocations: 0,
+        injected: 0,
+    };
+    let restore = Restore(FAULTS.with(|faults| faults.replace(Some(state))));
+    let result = f();
+    let state = FAULTS.with(Cell::get).unwrap_or(state);
+    drop(restore);
+    (result, state)
+}
+
+/// Whether an injected fault should fail this allocation
+fn inject_fault() -> bool {
+    FAULTS
+        .try_with(|faults| match faults.get() {
+            Some(mut state) => {
+                let fail = state.should_fail();
+                faults.set(Some(state));
+                fail
+            }
+            None => false,
+        })
+        .unwrap_or(false)
+}
+
+/// Account for `amount` more bytes, unless that would exceed a limit
+///
+/// `requested` is the size of the allocation, reported if it is refused.
+///
+/// This is also where injected faults fail allocations, without blaming a limit.
+fn reserve(amount: usize, requested: usize) -> bool {
+    if inject_fault() {
+        return false;
+    }
+    let live = GLOBAL_LIVE.fetch_add(amount, Ordering::SeqCst);
+    let global_limit = GLOBAL_LIMIT.load(Ordering::SeqCst);
+    let refused_by = if live.saturating_add(amount) > global_limit {
+        Some(global_limit)
+    } else {
+        // Thread locals may already be gone while a thread exits
+        BUDGET
+            .try_with(|budget| match budget.get() {
+                Some(b) if b.live.saturating_add(amount) > b.limit => Some(b.limit),
+                Some(b) => {
+                    budget.set(Some(Budget {
+                        live: b.live + amount,
+                        ..b
+                    }));
+                    None
+                }
+                None => None,
+            })
+            .unwrap_or(None)
+    };
+
+    match refused_by {
+        Some(limit) => {
+            GLOBAL_LIVE.fetch_sub(amount, Ordering::SeqCst);
+            let _ = LIMIT_FAILURE
+                .try_with(|failure| failure.set(Some(MemoryLimitExceeded { limit, requested })));
+            false
+        }
+        None => true,
+    }
+}
+
+/// Stop accounting for `amount` bytes
+fn release(amount: usize) {
+    GLOBAL_LIVE.fetch_sub(amount, Ordering::SeqCst);
+    let _ = BUDGET.try_with(|budget| {
+        if let Some(b) = budget.get() {
+            // Memory allocated before the limited call may be freed during it
+            budget.set(Some(Budget {
+                live: b.live.saturating_sub(amount),
+                ..b
+            }));
+        }
+    });
+}
+
+/// The number of blocks squash currently has allocated, across all threads
+pub(crate) fn blocks_in_use() -> usize {
+    GLOBAL_BLOCKS.load(Ordering::SeqCst)
+}
+
+/// The number of invalid pointers squash has tried to free or reallocate
+pub(crate) fn canary_failures() -> usize {
+    CANARY_FAILURES.load(Ordering::SeqCst)
+}
+
+/// Take whether an allocation failed on this thread, for any reason, since this was last called
+///
+/// Squash constructors return null on failure without saying why: this tells whether it was
+/// for lack of memory.
+pub(crate) fn take_allocation_failure() -> bool {
+    ALLOCATION_FAILED.try_with(Cell::take).unwrap_or(false)
+}
+
+/// Take the allocation most recently refused by a memory limit on this thread
+pub(crate) fn take_limit_failure() -> Option<MemoryLimitExceeded> {
+    LIMIT_FAILURE.try_with(Cell::take).unwrap_or(None)
+}
+
+unsafe fn install(funcs: SquashMemoryFuncs) -> Result<(), AlreadyInstalled> {
+    if INSTALLED.swap(true, Ordering::SeqCst) {
+        return Err(AlreadyInstalled);
+    }
+    squash_set_memory_functions(funcs);
+    Ok(())
+}
+
+// These are called from C, and must not unwind: the global allocator reports failure by
+// returning null, which is passed on to squash
+
+unsafe extern "C" fn rust_malloc(size: usize) -> *mut c_void {
+    allocate(size, MIN_ALIGN, false)
+}
+
+unsafe extern "C" fn rust_calloc(nmemb: usize, size: usize) -> *mut c_void {
+    match nmemb.checked_mul(size) {
+        Some(size) => allocate(size, MIN_ALIGN, true),
+        None => refuse(),
+    }
+}
+
+unsafe extern "C" fn rust_aligned_alloc(alignment: usize, size: usize) -> *mut c_void {
+    allocate(size, alignment, false)
+}
+
+unsafe extern "C" fn rust_realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    if ptr.is_null() {
+        return allocate(size, MIN_ALIGN, false);
+    }
+    let header = match read_header(ptr, "realloc") {
+        Some(header) => header,
+        None => return ptr::null_mut(),
+    };
+    let new_total = match size.checked_add(offset_of(header.align)) {
+        Some(total) if Layout::from_size_align(total, header.align).is_ok() => total,
+        _ => return refuse(),
+    };
+    let growth = size.saturating_sub(header.size);
+    if !reserve(growth, size) {
+        return refuse();
+    }
+    let base = rust_alloc::realloc(base_of(ptr, header), layout_of(header), new_total);
+    if base.is_null() {
+        release(growth);
+        return refuse();
+    }
+    release(header.size.saturating_sub(size));
+    record(|stats| {
+        stats.shrink(header.size);
+        stats.grow(size);
+        stats.reallocations += 1;
+    });
+    finish_allocation(base, size, header.align)
+}
+
+unsafe extern "C" fn rust_free(ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+    // Leak the block rather than free it with a bogus layout
+    let header = match read_header(ptr, "free") {
+        Some(header) => header,
+        None => return,
+    };
+    release(header.size);
+    GLOBAL_BLOCKS.fetch_sub(1, Ordering::SeqCst);
+    record(|stats| {
+        stats.shrink(header.size);
+        stats.deallocations += 1;
+    });
+    rust_alloc::dealloc(base_of(ptr, header), layout_of(header));
+}
+
+/// Fail an allocation, recording the failure for the error squash returns
+fn refuse() -> *mut c_void {
+    let _ = ALLOCATION_FAILED.try_with(|failed| failed.set(true));
+    ptr::null_mut()
+}
+
+/// Allocate `size` bytes aligned to `align`, preceded by a header
+///
+/// The header lives in the padding before the returned pointer, which is a multiple of the
+/// alignment, so the allocation stays aligned.
+unsafe fn allocate(size: usize, align: usize, zeroed: bool) -> *mut c_void {
+    let align = align.max(MIN_ALIGN);
+    let layout = match size
+        .checked_add(offset_of(align))
+        .and_then(|total| Layout::from_size_align(total, align).ok())
+    {
+        Some(layout) => layout,
+        None => return refuse(),
+    };
+    if !reserve(size, size) {
+        return refuse();
+    }
+    let base = if zeroed {
+        rust_alloc::alloc_zeroed(layout)
+    } else {
+        rust_alloc::alloc(layout)
+    };
+    if base.is_null() {
+        release(size);
+        return refuse();
+    }
+    GLOBAL_BLOCKS.fetch_add(1, Ordering::SeqCst);
+    record(|stats| {
+        stats.grow(size);
+        stats.allocations += 1;
+    });
+    finish_allocation(base, size, align)
+}
+
+unsafe fn finish_allocation(base: *mut u8, size: usize, align: usize) -> *mut c_void {
+    let ptr = base.add(offset_of(align));
+    (ptr as *mut Header).sub(1).write(Header {
+        canary: CANARY,
+        size,
+        align,
+    });
+    ptr as *mut c_void
+}
+
+/// Read the header of a block passed to `operation`, if it was allocated by us
+unsafe fn read_header(ptr: *mut c_void, operation: &str) -> Option<Header> {
+    let header = (ptr as *mut Header).sub(1).read();
+    if header.canary == CANARY {
+        return Some(header);
+    }
+    CANARY_FAILURES.fetch_add(1, Ordering::SeqCst);
+    // Only the canary allocator used for testing reports them: the plain one is kept quiet
+    if TRACKING.load(Ordering::SeqCst) {
+        let _ = writeln!(
+            io::stderr(),
+            "squash: {}: invalid pointer {:p} (canary {:#x} != {:#x})",
+            operation,
+            ptr,
+            header.canary,
+            CANARY
+        );
+    }
+    None
+}
+
+fn offset_of(align: usize) -> usize {
+    align.max(HEADER_SPACE)
+}
+
+unsafe fn base_of(ptr: *mut c_void, header: Header) -> *mut u8 {
+    (ptr as *mut u8).sub(offset_of(header.align))
+}
+
+unsafe fn layout_of(header: Header) -> Layout {
+    Layout::from_size_align_unchecked(header.size + offset_of(header.align), header.align)
+}
+
+// The header must fit in the space before every allocation. The space and alignments are
+// powers of two, so a space of at least `MIN_ALIGN` keeps allocations aligned
+const _: () = assert!(mem::size_of::<Header>() <= HEADER_SPACE && HEADER_SPACE >= MIN_ALIGN);
@@ -0,0 +1,589 @@
+//! Poll-based compression state shared by the async adapters
+//!
+//! The adapters for each async runtime wrap their inner reader or writer in a type
+//! implementing [`PollWrite`] or [`PollBufRead`], and drive an [`EncoderCore`] or
+//! [`DecoderCore`] with it. The adapters themselves are defined by [`async_adapters`], leaving
+//! only the runtime's async traits to implement.
+
+use std::io::{self, IoSlice};
+use std::task::{Context, Poll};
+
+use crate::totals::Counter;
+use crate::write::flush_unsupported;
+use crate::{Codec, Options, Progress, Result, Status, Stream, StreamType};
+
+const BUFFER_SIZE: usize = 32 * 1024;
+
+/// Define a runtime's `Encoder` and `Decoder`, with everything but their async trait impls
+///
+/// Names are resolved in the runtime's module, which must import its `AsyncWrite` and
+/// `AsyncBufRead` traits along with the types used here. `$poll_shutdown` is the `AsyncWrite`
+/// method finishing a writer, and `$finished` describes calling it, for the docs. Attributes
+/// before `Encoder` are added to its docs.
+macro_rules! async_adapters {
+    (
+        poll_shutdown: $poll_shutdown:ident,
+        finished: $finished:literal,
+        $(#[$encoder_attr:meta])*
+        Encoder
+    ) => {
+        /// Adapts the runtime's reader or writer to the runtime independent traits
+        #[derive(Debug)]
+        struct Io<T>(T);
+
+        impl<T: AsyncWrite + Unpin> PollWrite for Io<T> {
+            fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+                Pin::new(&mut self.0).poll_write(cx, buf)
+            }
+
+            fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.0).poll_flush(cx)
+            }
+
+            fn poll_shutdown(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Pin::new(&mut self.0).$poll_shutdown(cx)
+            }
+        }
+
+        impl<T: AsyncBufRead + Unpin> PollBufRead for Io<T> {
+            fn poll_fill_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+                Pin::new(&mut self.0).poll_fill_buf(cx)
+            }
+
+            fn consume(&mut self, amt: usize) {
+                Pin::new(&mut self.0).consume(amt)
+            }
+        }
+
+        /// Compresses data written to it, and writes the compressed data to an underlying
+        /// [`AsyncWrite`]
+        ///
+        /// Flushing behaves like the blocking [`Encoder`](crate::write::Encoder): codecs which
+        /// can't flush return an [`Unsupported`](io::ErrorKind::Unsupported) error, unless
+        /// [restarting on flush](Encoder::set_restart_on_flush) is enabled.
+        $(#[$encoder_attr])*
+        pub struct Encoder<W> {
+            inner: Io<W>,
+            core: EncoderCore,
+        }
+
+        impl<W: AsyncWrite + Unpin> Encoder<W> {
+            /// Create a new encoder compressing with `codec` into `inner`
+            pub fn new(codec: Codec, inner: W) -> io::Result<Self> {
+                Self::with_options(codec, inner, None)
+            }
+
+            /// Create a new encoder compressing with `codec` into `inner`, using `options`
+            pub fn with_options(
+                codec: Codec,
+                inner: W,
+                options: Option<&Options>,
+            ) -> io::Result<Self> {
+                Ok(Encoder {
+                    inner: Io(inner),
+                    core: EncoderCore::new(codec, options)?,
+                })
+            }
+
+            /// Whether flushing a codec which can't flush ends the current member and starts a
+            /// new one
+            pub fn restart_on_flush(&self) -> bool {
+                self.core.restart_on_flush()
+            }
+
+            /// Set whether flushing a codec which can't flush ends the current member and starts a
+            /// new one
+            pub fn set_restart_on_flush(&mut self, restart_on_flush: bool) {
+                self.core.set_restart_on_flush(restart_on_flush);
+            }
+
+            $crate::totals::progress_methods! {
+                counter: core.counter,
+                total_in: "The number of bytes written to the encoder so far",
+                total_out: "The number of compressed bytes written to the underlying writer so far",
+                every: "have been written to the encoder",
+            }
+
+            /// Get a reference to the underlying writer
+            pub fn get_ref(&self) -> &W {
+                &self.inner.0
+            }
+
+            /// Get a mutable reference to the underlying writer
+            ///
+            /// Writing directly to the underlying writer may corrupt the compressed stream.
+            pub fn get_mut(&mut self) -> &mut W {
+                &mut self.inner.0
+            }
+
+            /// Consume the encoder, returning the underlying writer
+            ///
+            #[doc = concat!(
+                "The compressed stream is incomplete unless the encoder was ",
+                $finished,
+                " first."
+            )]
+            pub fn into_inner(self) -> W {
+                self.inner.0
+            }
+        }
+
+        /// Decompresses data read from an underlying [`AsyncBufRead`]
+        ///
+        /// Concatenated members are handled like the blocking [`Decoder`](crate::read::Decoder).
+        pub struct Decoder<R> {
+            inner: Io<R>,
+            core: DecoderCore,
+        }
+
+        impl<R: AsyncBufRead + Unpin> Decoder<R> {
+            /// Create a new decoder decompressing data from `inner` with `codec`
+            pub fn new(codec: Codec, inner: R) -> io::Result<Self> {
+                Self::with_options(codec, inner, None)
+            }
+
+            /// Create a new decoder decompressing data from `inner` with `codec`, using `options`
+            pub fn with_options(
+                codec: Codec,
+                inner: R,
+                options: Option<&Options>,
+            ) -> io::Result<Self> {
+                Ok(Decoder {
+                    inner: Io(inner),
+                    core: DecoderCore::new(codec, options)?,
+                })
+            }
+
+            /// Whether the decoder continues decoding after the end of a compressed member
+            pub fn multi_member(&self) -> bool {
+                self.core.multi_member()
+            }
+
+            /// Set whether the decoder continues decoding after the end of a compressed member
+            pub fn set_multi_member(&mut self, multi_member: bool) {
+                self.core.set_multi_member(multi_member);
+            }
+
+            $crate::totals::progress_methods! {
+                counter: core.counter,
+                total_in: "The number of compressed bytes read from the underlying reader so far",
+                total_out: "The number of decompressed bytes produced so far",
+                every: "of compressed data have been read",
+            }
+
+            /// Get a reference to the underlying reader
+            pub fn get_ref(&self) -> &R {
+                &self.inner.0
+            }
+
+            /// Get a mutable reference to the underlying reader
+            ///
+            /// Reading directly from the underlying reader may corrupt the compressed stream.
+            pub fn get_mut(&mut self) -> &mut R {
+                &mut self.inner.0
+            }
+
+            /// Consume the decoder, returning the underlying reader
+            ///
+            /// Any decompressed data which hasn't been read yet is lost.
+            pub fn into_inner(self) -> R {
+                self.inner.0
+            }
+        }
+    };
+}
+
+pub(crate) use async_adapters;
+
+/// An async writer, independent of the runtime providing it
+pub(crate) trait PollWrite {
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+    fn poll_shutdown(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+}
+
+/// An async buffered reader, independent of the runtime providing it
+pub(crate) trait PollBufRead {
+    fn poll_fill_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>>;
+    fn consume(&mut self, amt: usize);
+}
+
+/// Output produced by squash which hasn't been passed on yet
+struct Buffer {
+    buf: Box<[u8]>,
+    pos: usize,
+    len: usize,
+}
+
+impl Buffer {
+    fn new() -> Self {
+        Buffer {
+            buf: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.len
+    }
+
+    fn pending(&self) -> &[u8] {
+        &self.buf[self.pos..self.len]
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.len);
+    }
+
+    /// Refill the (empty) buffer with the output of a stream operation
+    fn fill<F>(&mut self, operation: F) -> Result<Progress>
+    where
+        F: FnOnce(&mut [u8]) -> Result<Progress>,
+    {
+        debug_assert!(self.is_empty());
+        let progress = operation(&mut self.buf)?;
+        self.pos = 0;
+        self.len = progress.written;
+        Ok(progress)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum EncoderState {
+    Writing,
+    /// Flushing with `squash_stream_flush` until it stops returning `SQUASH_PROCESSING`
+    Flushing,
+    /// Finishing the current member to flush a codec which can't, before starting a new one
+    Restarting,
+    /// The flush is complete once the output is written
+    Flushed,
+    Finishing,
+    Finished,
+}
+
+/// Compression state for an async encoder
+///
+/// Output is only produced once the previous output has been written to the inner writer,
+/// so a slow writer holds back the stream instead of growing a buffer.
+pub(crate) struct EncoderCore {
+    stream: Stream,
+    out: Buffer,
+    state: EncoderState,
+    restart_on_flush: bool,
+    pending: bool,
+    pub(crate) counter: Counter,
+}
+
+impl EncoderCore {
+    pub(crate) fn new(codec: Codec, options: Option<&Options>) -> io::Result<Self> {
+        Ok(EncoderCore {
+            stream: Stream::new(codec, StreamType::Compress, options)?,
+            out: Buffer::new(),
+            state: EncoderState::Writing,
+            restart_on_flush: false,
+            pending: false,
+            counter: Counter::new(StreamType::Compress),
+        })
+    }
+
+    pub(crate) fn restart_on_flush(&self) -> bool {
+        self.restart_on_flush
+    }
+
+    pub(crate) fn set_restart_on_flush(&mut self, restart_on_flush: bool) {
+        self.restart_on_flush = restart_on_flush;
+    }
+
+    fn poll_drain<W: PollWrite>(
+        &mut self,
+        cx: &mut Context<'_>,
+        inner: &mut W,
+    ) -> Poll<io::Result<()>> {
+        while !self.out.is_empty() {
+            let written = match inner.poll_write(cx, self.out.pending()) {
+                Poll::Ready(Ok(written)) => written,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.out.consume(written);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    pub(crate) fn poll_write<W: PollWrite>(
+        &mut self,
+        cx: &mut Context<'_>,
+        inner: &mut W,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.state {
+            EncoderState::Writing => {}
+            // Complete a flush which was interrupted by the inner writer
+            EncoderState::Flushing | EncoderState::Restarting | EncoderState::Flushed => {
+                match self.poll_flush_stream(cx, inner) {
+                    Poll::Ready(Ok(())) => {}
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            EncoderState::Finishing | EncoderState::Finished => {
+                return Poll::Ready(Err(io::Error::other("write after shutdown")));
+            }
+        }
+
+        let mut consumed = 0;
+        loop {
+            match self.poll_drain(cx, inner) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                // Input already passed to squash has been written, as far as the caller knows
+                Poll::Pending if consumed > 0 => return Poll::Ready(Ok(consumed)),
+                Poll::Pending => return Poll::Pending,
+            }
+            if consumed == data.len() {
+                return Poll::Ready(Ok(consumed));
+            }
+
+            let stream = &mut self.stream;
+            let input = &data[consumed..];
+            let progress = self.out.fill(|buf| stream.process(input, buf))?;
+            self.counter.record(&progress);
+            consumed += progress.read;
+            self.pending |= progress.read > 0;
+            if progress.read == 0 && progress.written == 0 && progress.status != Status::Processing
+            {
+                return Poll::Ready(Ok(consumed));
+            }
+        }
+    }
+
+    pub(crate) fn poll_write_vectored<W: PollWrite>(
+        &mut self,
+        cx: &mut Context<'_>,
+        inner: &mut W,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let mut written = 0;
+        for buf in bufs.iter().filter(|buf| !buf.is_empty()) {
+            match self.poll_write(cx, inner, buf) {
+                Poll::Ready(Ok(len)) => {
+                    written += len;
+                    if len < buf.len() {
+                        break;
+                    }
+                }
+                Poll::Ready(Err(err)) if written == 0 => return Poll::Ready(Err(err)),
+                Poll::Pending if written == 0 => return Poll::Pending,
+                _ => break,
+            }
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    /// Flush the stream, without flushing the inner writer
+    fn poll_flush_stream<W: PollWrite>(
+        &mut self,
+        cx: &mut Context<'_>,
+        inner: &mut W,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            match self.poll_drain(cx, inner) {
+                Poll::Ready(Ok(())) => {}
+                other => return other,
+            }
+            match self.state {
+                EncoderState::Writing => {
+                    let codec = self.stream.codec();
+                    self.state = if codec.can_flush() {
+                        EncoderState::Flushing
+                    } else if !self.restart_on_flush {
+                        return Poll::Ready(Err(flush_unsupported(codec)));
+                    } else if self.pending {
+                        EncoderState::Restarting
+                    } else {
+                        EncoderState::Flushed
+                    };
+                }
+                EncoderState::Flushing => {
+                    let stream = &mut self.stream;
+                    let progress = self.out.fill(|buf| stream.flush(&[], buf))?;
+                    self.counter.record(&progress);
+                    if progress.status != Status::Processing {
+                        self.state = EncoderState::Flushed;
+                    }
+                }
+                EncoderState::Restarting => {
+                    let stream = &mut self.stream;
+                    let progress = self.out.fill(|buf| stream.finish(&[], buf))?;
+                    self.counter.record(&progress);
+                    if progress.status != Status::Processing {
+                        let options = self.stream.shared_options();
+                        self.stream = Stream::new(
+                            self.stream.codec(),
+                            StreamType::Compress,
+                            options.as_ref(),
+                        )?;
+                        self.state = EncoderState::Flushed;
+                    }
+                }
+                EncoderState::Flushed => {
+                    self.state = EncoderState::Writing;
+                    self.pending = false;
+                    return Poll::Ready(Ok(()));
+                }
+                EncoderState::Finishing | EncoderState::Finished => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+
+    pub(crate) fn poll_flush<W: PollWrite>(
+        &mut self,
+        cx: &mut Context<'_>,
+        inner: &mut W,
+    ) -> Poll<io::Result<()>> {
+        match self.poll_flush_stream(cx, inner) {
+            Poll::Ready(Ok(())) => inner.poll_flush(cx),
+            other => other,
+        }
+    }
+
+    pub(crate) fn poll_shutdown<W: PollWrite>(
+        &mut self,
+        cx: &mut Context<'_>,
+        inner: &mut W,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            match self.poll_drain(cx, inner) {
+                Poll::Ready(Ok(())) => {}
+                other => return other,
+            }
+            match self.state {
+                EncoderState::Writing | EncoderState::Flushed => {
+                    self.state = EncoderState::Finishing;
+                }
+                EncoderState::Flushing | EncoderState::Restarting => {
+                    match self.poll_flush_stream(cx, inner) {
+                        Poll::Ready(Ok(())) => {}
+                        other => return other,
+                    }
+                }
+                EncoderState::Finishing => {
+                    let stream = &mut self.stream;
+                    let progress = self.out.fill(|buf| stream.finish(&[], buf))?;
+                    self.counter.record(&progress);
+                    if progress.status != Status::Processing {
+                        self.state = EncoderState::Finished;
+                    }
+                }
+                EncoderState::Finished => return inner.poll_shutdown(cx),
+            }
+        }
+    }
+}
+
+/// Decompression state for an async decoder
+///
+/// Decompressed output is buffered, so decoders can implement buffered reading.
+pub(crate) struct DecoderCore {
+    stream: Stream,
+    out: Buffer,
+    multi_member: bool,
+    member_ended: bool,
+    // The last call to `process` had more output to give
+    processing: bool,
+    done: bool,
+    pub(crate) counter: Counter,
+}
+
+impl DecoderCore {
+    pub(crate) fn new(codec: Codec, options: Option<&Options>) -> io::Result<Self> {
+        Ok(DecoderCore {
+            stream: Stream::new(codec, StreamType::Decompress, options)?,
+            out: Buffer::new(),
+            multi_member: codec.supports_concatenation(),
+            member_ended: false,
+            processing: false,
+            done: false,
+            counter: Counter::new(StreamType::Decompress),
+        })
+    }
+
+    pub(crate) fn multi_member(&self) -> bool {
+        self.multi_member
+    }
+
+    pub(crate) fn set_multi_member(&mut self, multi_member: bool) {
+        self.multi_member = multi_member;
+    }
+
+    pub(crate) fn poll_fill_buf<R: PollBufRead>(
+        &mut self,
+        cx: &mut Context<'_>,
+        inner: &mut R,
+    ) -> Poll<io::Result<&[u8]>> {
+        while self.out.is_empty() && !self.done {
+            if self.member_ended && !self.multi_member {
+                self.done = true;
+                break;
+            }
+            // Output held back by the stream is drained before polling for more input, which
+            // might not arrive until the output has been read
+            if self.processing {
+                let stream = &mut self.stream;
+                let progress = self.out.fill(|buf| stream.process(&[], buf))?;
+                self.counter.record(&progress);
+                self.processing = progress.status == Status::Processing;
+                self.member_ended = progress.status == Status::EndOfStream;
+                continue;
+            }
+            let input = match inner.poll_fill_buf(cx) {
+                Poll::Ready(Ok(input)) => input,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+            let eof = input.is_empty();
+
+            if self.member_ended {
+                if eof {
+                    self.done = true;
+                } else {
+                    let options = self.stream.shared_options();
+                    self.stream = Stream::new(
+                        self.stream.codec(),
+                        StreamType::Decompress,
+                        options.as_ref(),
+                    )?;
+                    self.member_ended = false;
+                }
+                continue;
+            }
+
+            let stream = &mut self.stream;
+            let progress = self.out.fill(|buf| {
+                if eof {
+                    stream.finish(input, buf)
+                } else {
+                    stream.process(input, buf)
+                }
+            })?;
+            inner.consume(progress.read);
+            self.counter.record(&progress);
+            self.processing = !eof && progress.status == Status::Processing;
+
+            match progress.status {
+                Status::EndOfStream => self.member_ended = true,
+                Status::Ok if eof => self.done = true,
+                _ => {}
+            }
+        }
+        Poll::Ready(Ok(self.out.pending()))
+    }
+
+    pub(crate) fn consume(&mut self, amt: usize) {
+        self.out.consume(amt);
+    }
+}
@@ -0,0 +1,163 @@
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_void;
+use std::ptr::{self, NonNull};
+use std::{fmt, mem, slice};
+
+use squash_sys::*;
+
+use crate::error::{out_of_memory, Error, Result};
+
+/// A zero-initialised byte buffer with a chosen alignment, allocated with
+/// `squash_aligned_alloc`
+///
+/// Codecs using SIMD instructions can be faster when their input and output are aligned.
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    align: usize,
+}
+
+// The buffer owns its memory, which squash's allocator doesn't tie to a thread
+unsafe impl Send for AlignedBuf {}
+unsafe impl Sync for AlignedBuf {}
+
+impl AlignedBuf {
+    /// Allocate a buffer of `len` zero bytes, aligned to `align` bytes
+    ///
+    /// `align` must be a power of two, and at least the size of a pointer.
+    pub fn new(len: usize, align: usize) -> Result<Self> {
+        if !align.is_power_of_two() || align < mem::size_of::<*const c_void>() {
+            return Err(Error::from_raw(SquashStatus::SQUASH_BAD_PARAM));
+        }
+        // C11 `aligned_alloc` requires a size which is a non-zero multiple of the alignment
+        let size = match len.max(1).checked_add(align - 1) {
+            Some(size) => size & !(align - 1),
+            None => return Err(Error::from_raw(SquashStatus::SQUASH_RANGE)),
+        };
+        let ptr = unsafe { squash_aligned_alloc(align, size) } as *mut u8;
+        let ptr = NonNull::new(ptr).ok_or_else(out_of_memory)?;
+        unsafe { ptr::write_bytes(ptr.as_ptr(), 0, len) };
+        Ok(AlignedBuf { ptr, len, align })
+    }
+
+    /// Allocate a buffer aligned to `align` bytes, holding a copy of `data`
+    pub fn from_slice(data: &[u8], align: usize) -> Result<Self> {
+        let mut buf = Self::new(data.len(), align)?;
+        buf.copy_from_slice(data);
+        Ok(buf)
+    }
+
+    /// The alignment of the buffer
+    pub fn align(&self) -> usize {
+        self.align
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.len)
+            .field("align", &self.align)
+            .finish()
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe {
+            squash_aligned_free(self.ptr.as_ptr() as *mut c_void);
+        }
+    }
+}
+
+/// A value allocated with `squash_malloc`, and freed with `squash_free` when dropped
+///
+/// This lets memory allocated by squash be used from Rust without copying it. Only byte
+/// slices, `SquashBox<[u8]>`, can be created so far.
+pub struct SquashBox<T: ?Sized> {
+    ptr: NonNull<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for SquashBox<T> {}
+unsafe impl<T: ?Sized + Sync> Sync for SquashBox<T> {}
+
+impl SquashBox<[u8]> {
+    /// Allocate `len` zero bytes with `squash_calloc`
+    pub fn new_zeroed(len: usize) -> Result<Self> {
+        // `calloc(0)` may return null
+        let ptr = unsafe { squash_calloc(1, len.max(1)) } as *mut u8;
+        let ptr = NonNull::new(ptr).ok_or_else(out_of_memory)?;
+        Ok(unsafe { Self::from_raw_parts(ptr.as_ptr(), len) }.unwrap())
+    }
+
+    /// Allocate a copy of `data` with `squash_malloc`
+    pub fn from_slice(data: &[u8]) -> Result<Self> {
+        let ptr = unsafe { squash_malloc(data.len().max(1)) } as *mut u8;
+        let ptr = NonNull::new(ptr).ok_or_else(out_of_memory)?;
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), ptr.as_ptr(), data.len());
+            Ok(Self::from_raw_parts(ptr.as_ptr(), data.len()).unwrap())
+        }
+    }
+
+    /// Take ownership of `len` bytes at `ptr`, returning `None` if `ptr` is null
+    ///
+    /// # Safety
+    /// `ptr` must have been allocated by `squash_malloc`, `squash_calloc` or `squash_realloc`,
+    /// with at least `len` bytes, all initialised. Nothing else may use or free it afterwards.
+    pub unsafe fn from_raw_parts(ptr: *mut u8, len: usize) -> Option<Self> {
+        NonNull::new(ptr::slice_from_raw_parts_mut(ptr, len)).map(|ptr| SquashBox { ptr })
+    }
+}
+
+impl<T: ?Sized> SquashBox<T> {
+    /// Give up ownership of the value, which must later be freed with `squash_free`
+    pub fn into_raw(self) -> *mut T {
+        let ptr = self.ptr.as_ptr();
+        mem::forget(self);
+        ptr
+    }
+}
+
+impl<T: ?Sized> Deref for SquashBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SquashBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SquashBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Drop for SquashBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.ptr.as_ptr());
+            squash_free(self.ptr.as_ptr() as *mut u8 as *mut c_void);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Compression adapters for streams of [`Bytes`] chunks
+//!
+//! [`compress_stream`] and [`decompress_stream`] wrap a
+//! [`Stream`](futures_core::Stream) of chunks, like an HTTP body, in a stream of compressed or
+//! decompressed chunks.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::{io, mem};
+
+use ::bytes::{Buf, Bytes};
+use ::futures_core::Stream as FuturesStream;
+
+use crate::{Codec, Options, Progress, Result, Status, Stream, StreamType};
+
+const DEFAULT_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Compress the chunks of `stream` with `codec`
+///
+/// The stream is finished once `stream` ends.
+pub fn compress_stream<S>(codec: Codec, stream: S) -> io::Result<ChunkStream<S>>
+where
+    S: FuturesStream<Item = io::Result<Bytes>> + Unpin,
+{
+    ChunkStream::with_options(codec, StreamType::Compress, stream, None)
+}
+
+/// Decompress the chunks of `stream` with `codec`
+pub fn decompress_stream<S>(codec: Codec, stream: S) -> io::Result<ChunkStream<S>>
+where
+    S: FuturesStream<Item = io::Result<Bytes>> + Unpin,
+{
+    ChunkStream::with_options(codec, StreamType::Decompress, stream, None)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Reading,
+    MemberEnded,
+    Finishing,
+    Done,
+}
+
+/// A stream of chunks compressed or decompressed from another stream of chunks
+///
+/// Created by [`compress_stream`] and [`decompress_stream`]. Output is collected into chunks of
+/// [`chunk_size`](ChunkStream::chunk_size) bytes. A smaller chunk is produced at the end of
+/// the stream, or when the input stream has nothing ready, so that data isn't held back
+/// waiting for more input.
+///
+/// Errors from the input stream are passed through, and the stream ends after any error from
+/// squash.
+pub struct ChunkStream<S> {
+    inner: S,
+    stream: Stream,
+    input: Bytes,
+    out: Vec<u8>,
+    chunk_size: usize,
+    multi_member: bool,
+    // The last call to `process` had more output to give
+    processing: bool,
+    state: State,
+}
+
+impl<S> ChunkStream<S>
+where
+    S: FuturesStream<Item = io::Result<Bytes>> + Unpin,
+{
+    /// Compress or decompress the chunks of `stream` with `codec`, using `options`
+    pub fn with_options(
+        codec: Codec,
+        stream_type: StreamType,
+        stream: S,
+        options: Option<&Options>,
+    ) -> io::Result<Self> {
+        Ok(ChunkStream {
+            inner: stream,
+            stream: Stream::new(codec, stream_type, options)?,
+            input: Bytes::new(),
+            out: Vec::with_capacity(DEFAULT_CHUNK_SIZE),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            multi_member: codec.supports_concatenation(),
+            processing: false,
+            state: State::Reading,
+        })
+    }
+
+    /// The maximum size of output chunks
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Set the maximum size of output chunks
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is zero.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        assert!(chunk_size > 0, "chunk size must be non-zero");
+        self.chunk_size = chunk_size;
+    }
+
+    /// Whether decompression continues after the end of a compressed member
+    ///
+    /// See [`Decoder::multi_member`](crate::read::Decoder::multi_member). This has no effect
+    /// when compressing.
+    pub fn multi_member(&self) -> bool {
+        self.multi_member
+    }
+
+    /// Set whether decompression continues after the end of a compressed member
+    pub fn set_multi_member(&mut self, multi_member: bool) {
+        self.multi_member = multi_member;
+    }
+
+    /// Get a reference to the input stream
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Consume the adapter, returning the input stream
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Append the output of a stream operation to the output chunk
+    fn fill<F>(&mut self, operation: F) -> Result<Progress>
+    where
+        F: FnOnce(&mut Stream, &[u8], &mut [u8]) -> Result<Progress>,
+    {
+        let len = self.out.len();
+        self.out.resize(self.chunk_size.max(len), 0);
+        let result = operation(&mut self.stream, &self.input, &mut self.out[len..]);
+        let written = result.as_ref().map_or(0, |progress| progress.written);
+        self.out.truncate(len + written);
+        if result.is_err() {
+            self.state = State::Done;
+        }
+        result
+    }
+
+    fn take_chunk(&mut self) -> Bytes {
+        Bytes::from(mem::replace(
+            &mut self.out,
+            Vec::with_capacity(self.chunk_size),
+        ))
+    }
+}
+
+impl<S> FuturesStream for ChunkStream<S>
+where
+    S: FuturesStream<Item = io::Result<Bytes>> + Unpin,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.out.len() >= this.chunk_size {
+                return Poll::Ready(Some(Ok(this.take_chunk())));
+            }
+            match this.state {
+                State::Done if this.out.is_empty() => return Poll::Ready(None),
+                State::Done => return Poll::Ready(Some(Ok(this.take_chunk()))),
+                State::Finishing => {
+                    let progress = this.fill(|stream, _, out| stream.finish(&[], out))?;
+                    if progress.status != Status::Processing {
+                        this.state = State::Done;
+                    }
+                }
+                State::MemberEnded if !this.multi_member => this.state = State::Done,
+                // Output held back by the stream is drained before polling for more input
+                State::Reading | State::MemberEnded
+                    if this.input.is_empty() && !this.processing =>
+                {
+                    match Pin::new(&mut this.inner).poll_next(cx) {
+                        Poll::Ready(Some(Ok(chunk))) => this.input = chunk,
+                        Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                        Poll::Ready(None) if this.state == State::Reading => {
+                            this.state = State::Finishing
+                        }
+                        Poll::Ready(None) => this.state = State::Done,
+                        Poll::Pending if this.out.is_empty() => return Poll::Pending,
+                        Poll::Pending => return Poll::Ready(Some(Ok(this.take_chunk()))),
+                    }
+                }
+                State::MemberEnded => {
+                    let options = this.stream.shared_options();
+                    this.stream = Stream::new(
+                        this.stream.codec(),
+                        this.stream.stream_type(),
+                        options.as_ref(),
+                    )?;
+                    this.state = State::Reading;
+                }
+                State::Reading => {
+                    let progress = this.fill(|stream, input, out| stream.process(input, out))?;
+                    this.input.advance(progress.read);
+                    this.processing = progress.status == Status::Processing;
+                    if progress.status == Status::EndOfStream {
+                        this.state = State::MemberEnded;
+                    }
+                }
+            }
+        }
+    }
+}
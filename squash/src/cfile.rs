@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::raw::c_char;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::ptr::NonNull;
+
+use squash_sys::FILE;
+
+/// An owned C `FILE*`, closed on drop
+pub(crate) struct CFile(NonNull<FILE>);
+
+impl CFile {
+    /// Open a C stream on a duplicate of `file`'s descriptor
+    ///
+    /// The duplicate shares its offset with `file`, but closing it leaves `file` open. `mode` is
+    /// a nul-terminated `fdopen` mode.
+    pub(crate) fn dup(file: &File, mode: &'static [u8]) -> io::Result<Self> {
+        let fd = unsafe { libc::dup(file.as_raw_fd()) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        unsafe { CFile::from_raw_fd(fd, mode) }
+    }
+
+    /// Open a C stream on `fd` with a mode matching its access mode, taking ownership of it
+    ///
+    /// `fd` is closed if the stream can't be opened.
+    pub(crate) unsafe fn from_raw_fd_any(fd: RawFd) -> io::Result<Self> {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags < 0 {
+            let err = io::Error::last_os_error();
+            libc::close(fd);
+            return Err(err);
+        }
+        let mode: &'static [u8] = match flags & libc::O_ACCMODE {
+            libc::O_RDONLY => b"rb\0",
+            libc::O_WRONLY => b"wb\0",
+            _ => b"r+b\0",
+        };
+        CFile::from_raw_fd(fd, mode)
+    }
+
+    /// Open a C stream on `fd`, taking ownership of it
+    ///
+    /// `fd` is closed if the stream can't be opened.
+    pub(crate) unsafe fn from_raw_fd(fd: RawFd, mode: &'static [u8]) -> io::Result<Self> {
+        debug_assert_eq!(mode.last(), Some(&0));
+        match NonNull::new(libc::fdopen(fd, mode.as_ptr() as *const c_char)) {
+            Some(fp) => Ok(CFile(fp)),
+            None => {
+                let err = io::Error::last_os_error();
+                libc::close(fd);
+                Err(err)
+            }
+        }
+    }
+
+    /// Take ownership of a C stream
+    pub(crate) unsafe fn from_raw(fp: *mut FILE) -> Option<Self> {
+        NonNull::new(fp).map(CFile)
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut FILE {
+        self.0.as_ptr()
+    }
+
+    /// Give up ownership of the stream without closing it
+    pub(crate) fn into_raw(self) -> *mut FILE {
+        let fp = self.as_ptr();
+        mem::forget(self);
+        fp
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        if unsafe { libc::fflush(self.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// The logical position of the stream, taking buffered data into account
+    pub(crate) fn position(&self) -> io::Result<u64> {
+        let position = unsafe { libc::ftello(self.as_ptr()) };
+        if position < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(position as u64)
+    }
+
+    /// Flush and close the stream, returning a duplicate of its descriptor
+    pub(crate) fn into_file(mut self) -> io::Result<File> {
+        self.flush()?;
+        let fd = unsafe { libc::dup(libc::fileno(self.as_ptr())) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let file = unsafe { File::from_raw_fd(fd) };
+        self.close()?;
+        Ok(file)
+    }
+
+    /// Close the stream, reporting any error flushing buffered data
+    pub(crate) fn close(self) -> io::Result<()> {
+        let fp = self.into_raw();
+        if unsafe { libc::fclose(fp) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CFile {
+    fn drop(&mut self) {
+        unsafe {
+            libc::fclose(self.as_ptr());
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use std::ffi::{CStr, OsString};
+use std::io::IoSlice;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::{fmt, str};
+
+use squash_sys::*;
+
+use crate::context::Context;
+use crate::error::{check, Result, Status};
+use crate::options::{options_ptr, Options};
+use crate::plugin::Plugin;
+use crate::stream::{Stream, StreamType};
+
+/// Codecs whose formats define the concatenation of several compressed members as a valid
+/// stream, which decompresses to the concatenation of the members' contents
+const CONCATENATING_CODECS: &[&str] = &["gzip", "bzip2", "xz", "zstd"];
+
+/// Single extensions standing for a compressed tarball, with the extension of the codec
+///
+/// Extensions are compared ignoring case, so GNU tar's `.taz` (gzip) is left out: it can't be
+/// told apart from `.taZ` (compress).
+const TARBALL_EXTENSIONS: &[(&str, &str)] = &[
+    ("tgz", "gz"),
+    ("tbz", "bz2"),
+    ("tbz2", "bz2"),
+    ("txz", "xz"),
+    ("tlz", "lzma"),
+    ("tzst", "zst"),
+];
+
+/// A compression codec provided by a squash plugin
+///
+/// Codecs are owned by the default squash context, and live for the rest of the program.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Codec(NonNull<SquashCodec>);
+
+// Codecs are never freed, and are safe to use from multiple threads
+unsafe impl Send for Codec {}
+unsafe impl Sync for Codec {}
+
+impl Codec {
+    /// Find a codec by name
+    pub fn find(name: &str) -> Option<Self> {
+        Context::get_default().codec(name)
+    }
+
+    /// Find a codec by the file extension it uses (without the leading `.`)
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Context::get_default().codec_from_extension(extension)
+    }
+
+    /// Find a codec from the extension of a compressed file's name
+    ///
+    /// Returns the codec along with the name of the file it decompresses to: the extension is
+    /// stripped, so `logs.tar.zst` gives `logs.tar`, and the single extensions for compressed
+    /// tarballs (`.tgz`, `.tbz2`, `.txz` and so on) are replaced with `.tar`.
+    ///
+    /// Squash matches extensions exactly, so the extension is looked up as written, then in
+    /// lowercase: `.GZ` finds gzip, while compress keeps its `.Z`.
+    pub fn from_path(path: &Path) -> Option<(Self, PathBuf)> {
+        let extension = path.extension()?.to_str()?;
+        let lowercase = extension.to_ascii_lowercase();
+        let tarball = TARBALL_EXTENSIONS
+            .iter()
+            .find(|&&(tarball, _)| tarball == lowercase);
+        let codec = match tarball {
+            Some(&(_, codec_extension)) => Codec::from_extension(codec_extension)?,
+            None => {
+                Codec::from_extension(extension).or_else(|| Codec::from_extension(&lowercase))?
+            }
+        };
+        let output = match tarball {
+            Some(_) => path.with_extension("tar"),
+            None => path.with_extension(""),
+        };
+        Some((codec, output))
+    }
+
+    /// The name of the file `path` compresses to, with the codec's extension appended
+    ///
+    /// Returns `None` if the codec has no extension.
+    pub fn output_path(self, path: &Path) -> Option<PathBuf> {
+        let mut output = OsString::from(path);
+        output.push(".");
+        output.push(self.extension()?);
+        Some(output.into())
+    }
+
+    /// Wrap a raw codec pointer
+    ///
+    /// # Safety
+    /// `codec` must be null, or point to a codec owned by a squash context which outlives
+    /// the program.
+    pub unsafe fn from_raw(codec: *mut SquashCodec) -> Option<Self> {
+        NonNull::new(codec).map(Codec)
+    }
+
+    /// Access the raw `SquashCodec` pointer
+    pub fn as_ptr(self) -> *mut SquashCodec {
+        self.0.as_ptr()
+    }
+
+    /// The name of the codec
+    pub fn name(self) -> &'static str {
+        unsafe { static_str(squash_codec_get_name(self.as_ptr())) }.unwrap_or("")
+    }
+
+    /// The file extension used by the codec (without the leading `.`), if it has one
+    pub fn extension(self) -> Option<&'static str> {
+        unsafe { static_str(squash_codec_get_extension(self.as_ptr())) }
+    }
+
+    /// The plugin providing the codec
+    pub fn plugin(self) -> Plugin {
+        unsafe { Plugin::from_raw(squash_codec_get_plugin(self.as_ptr())) }
+            .expect("squash codecs always belong to a plugin")
+    }
+
+    /// Information about the capabilities of the codec
+    pub fn info(self) -> SquashCodecInfo {
+        unsafe { squash_codec_get_info(self.as_ptr()) }
+    }
+
+    fn has_info(self, flag: SquashCodecInfo) -> bool {
+        (self.info() & flag) == flag
+    }
+
+    /// Whether streams for the codec can be flushed
+    pub fn can_flush(self) -> bool {
+        self.has_info(SquashCodecInfo::SQUASH_CODEC_INFO_CAN_FLUSH)
+    }
+
+    /// Whether the codec is able to extract the decompressed size from compressed data
+    pub fn knows_uncompressed_size(self) -> bool {
+        self.has_info(SquashCodecInfo::SQUASH_CODEC_INFO_KNOWS_UNCOMPRESSED_SIZE)
+    }
+
+    /// Whether the codec's format allows several compressed members to be concatenated
+    ///
+    /// Decompressing such a stream produces the concatenation of each member's contents, like
+    /// `cat a.gz b.gz > all.gz` produces a valid gzip file.
+    pub fn supports_concatenation(self) -> bool {
+        CONCATENATING_CODECS.contains(&self.name())
+    }
+
+    /// The maximum size of the compressed output for input of `uncompressed_size` bytes
+    pub fn max_compressed_size(self, uncompressed_size: usize) -> usize {
+        unsafe { squash_codec_get_max_compressed_size(self.as_ptr(), uncompressed_size) }
+    }
+
+    /// The size `compressed` will decompress to, if the codec is able to tell
+    pub fn uncompressed_size(self, compressed: &[u8]) -> Option<usize> {
+        if !self.knows_uncompressed_size() {
+            return None;
+        }
+        let size = unsafe {
+            squash_codec_get_uncompressed_size(self.as_ptr(), compressed.len(), compressed.as_ptr())
+        };
+        if size == 0 && !compressed.is_empty() {
+            None
+        } else {
+            Some(size)
+        }
+    }
+
+    /// Compress `input` into `output`, returning the size of the compressed data
+    pub fn compress_into(
+        self,
+        input: &[u8],
+        output: &mut [u8],
+        options: Option<&Options>,
+    ) -> Result<usize> {
+        let mut output_len = output.len();
+        check(unsafe {
+            squash_codec_compress_with_options(
+                self.as_ptr(),
+                &mut output_len,
+                output.as_mut_ptr(),
+                input.len(),
+                input.as_ptr(),
+                options_ptr(options),
+            )
+        })?;
+        Ok(output_len)
+    }
+
+    /// Compress `input` into a new buffer
+    pub fn compress(self, input: &[u8], options: Option<&Options>) -> Result<Vec<u8>> {
+        let mut output = vec![0; self.max_compressed_size(input.len())];
+        let len = self.compress_into(input, &mut output, options)?;
+        output.truncate(len);
+        Ok(output)
+    }
+
+    /// Compress the concatenation of the slices in `input` into a new buffer
+    ///
+    /// Codecs with native streaming support are fed each slice in turn, without copying. Other
+    /// codecs can only compress a single buffer, so the slices are copied into one first.
+    pub fn compress_vectored(
+        self,
+        input: &[IoSlice<'_>],
+        options: Option<&Options>,
+    ) -> Result<Vec<u8>> {
+        if let [input] = input {
+            return self.compress(input, options);
+        }
+        let total = input.iter().map(|slice| slice.len()).sum();
+        if !self.has_info(SquashCodecInfo::SQUASH_CODEC_INFO_NATIVE_STREAMING) {
+            let mut joined = Vec::with_capacity(total);
+            for slice in input {
+                joined.extend_from_slice(slice);
+            }
+            return self.compress(&joined, options);
+        }
+
+        let mut stream = Stream::new(self, StreamType::Compress, options)?;
+        let mut output = vec![0; self.max_compressed_size(total)];
+        let mut len = 0;
+        let mut drive = |mut input: &[u8], finish: bool| -> Result<()> {
+            loop {
+                if len == output.len() {
+                    output.resize(len * 2 + 4096, 0);
+                }
+                let progress = if finish {
+                    stream.finish(input, &mut output[len..])?
+                } else {
+                    stream.process(input, &mut output[len..])?
+                };
+                len += progress.written;
+                input = &input[progress.read..];
+                if progress.status != Status::Processing && input.is_empty() {
+                    return Ok(());
+                }
+            }
+        };
+        for slice in input {
+            drive(slice, false)?;
+        }
+        drive(&[], true)?;
+        output.truncate(len);
+        Ok(output)
+    }
+
+    /// Decompress `input` into `output`, returning the size of the decompressed data
+    pub fn decompress_into(
+        self,
+        input: &[u8],
+        output: &mut [u8],
+        options: Option<&Options>,
+    ) -> Result<usize> {
+        let mut output_len = output.len();
+        check(unsafe {
+            squash_codec_decompress_with_options(
+                self.as_ptr(),
+                &mut output_len,
+                output.as_mut_ptr(),
+                input.len(),
+                input.as_ptr(),
+                options_ptr(options),
+            )
+        })?;
+        Ok(output_len)
+    }
+
+    /// Decompress `input` into a new buffer
+    ///
+    /// If the codec can't tell how large the decompressed data will be, it is decompressed
+    /// with a stream, growing the buffer as needed.
+    pub fn decompress(self, input: &[u8], options: Option<&Options>) -> Result<Vec<u8>> {
+        if let Some(size) = self.uncompressed_size(input) {
+            let mut output = vec![0; size];
+            let len = self.decompress_into(input, &mut output, options)?;
+            output.truncate(len);
+            return Ok(output);
+        }
+
+        let mut stream = Stream::new(self, StreamType::Decompress, options)?;
+        let mut output = Vec::new();
+        let mut input = input;
+        loop {
+            let start = output.len();
+            output.resize(start + input.len().max(4096) * 2, 0);
+            let progress = if input.is_empty() {
+                stream.finish(input, &mut output[start..])?
+            } else {
+                stream.process(input, &mut output[start..])?
+            };
+            output.truncate(start + progress.written);
+            input = &input[progress.read..];
+
+            match progress.status {
+                Status::Processing => {}
+                Status::EndOfStream => break,
+                Status::Ok if input.is_empty() && progress.read == 0 => break,
+                Status::Ok => {}
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// Borrow a string owned by squash for the life of the program
+pub(crate) unsafe fn static_str(s: *const std::os::raw::c_char) -> Option<&'static str> {
+    if s.is_null() {
+        None
+    } else {
+        str::from_utf8(CStr::from_ptr(s).to_bytes()).ok()
+    }
+}
+
+impl fmt::Debug for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Codec").field(&self.name()).finish()
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
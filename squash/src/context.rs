@@ -0,0 +1,296 @@
+use std::ffi::CString;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::{env, error, fmt};
+
+use squash_sys::*;
+
+use crate::codec::Codec;
+use crate::error::Result as SquashResult;
+use crate::plugin::{push_codec, Plugin};
+
+/// The state of the default context's search path
+static SEARCH_PATH: Mutex<SearchPath> = Mutex::new(SearchPath {
+    created: false,
+    directories: None,
+});
+
+struct SearchPath {
+    /// Whether the default context has been created, after which the path cannot change
+    created: bool,
+    /// The directories set with [`Context::set_search_path`], if any
+    directories: Option<Vec<PathBuf>>,
+}
+
+fn search_path() -> MutexGuard<'static, SearchPath> {
+    SEARCH_PATH.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The environment variable squash reads its plugin search path from
+const PLUGINS_VAR: &str = "SQUASH_PLUGINS";
+
+/// The squash context, which discovers the installed plugins and the codecs they provide
+///
+/// The default context is created on first use, and lives for the rest of the program.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Context(NonNull<SquashContext>);
+
+// The default context is never freed, and squash guards its plugin tables with a lock
+unsafe impl Send for Context {}
+unsafe impl Sync for Context {}
+
+impl Context {
+    /// The default context, created on first use
+    pub fn get_default() -> Self {
+        search_path().created = true;
+        let context = unsafe { squash_context_get_default() };
+        Context(NonNull::new(context).expect("squash failed to create the default context"))
+    }
+
+    /// Add directories to the search path of the default context
+    ///
+    /// Plugins are looked for in the existing search path first, then in each of `directories`
+    /// in order. The existing search path is `SQUASH_PLUGINS` if it is set, and otherwise
+    /// squash's built-in plugin directory, as reported by pkg-config when this crate was
+    /// built. If pkg-config didn't report one, only `directories` are searched.
+    ///
+    /// The merged path is passed to `squash_set_default_search_path`. Squash prefers
+    /// `SQUASH_PLUGINS` when it is set, so the variable is then set to the merged path too,
+    /// which child processes inherit.
+    ///
+    /// This must be called before the default context is created, which happens on first use
+    /// of [`Context::get_default`] or [`Codec::find`]: later changes would be silently ignored
+    /// by squash, so they return [`SearchPathError::ContextCreated`]. Squash functions called
+    /// directly through [`squash_sys`] also create the default context without this knowing.
+    ///
+    /// # Safety
+    /// When `SQUASH_PLUGINS` is set, this modifies the environment, which is unsound while
+    /// another thread reads or writes it, including squash reading `SQUASH_PLUGINS` with
+    /// `getenv`. Call it before spawning any threads, e.g. at the start of `main`.
+    pub unsafe fn set_search_path<I, P>(directories: I) -> Result<(), SearchPathError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut search_path = search_path();
+        if search_path.created {
+            return Err(SearchPathError::ContextCreated);
+        }
+
+        let plugins = env::var_os(PLUGINS_VAR);
+        let mut path: Vec<PathBuf> = match &plugins {
+            Some(plugins) => env::split_paths(plugins).collect(),
+            None => builtin_directory().into_iter().collect(),
+        };
+        for directory in directories {
+            let directory = directory.as_ref();
+            if !directory.is_dir() {
+                return Err(SearchPathError::NotFound(directory.to_owned()));
+            }
+            path.push(directory.to_owned());
+        }
+
+        if let Some(invalid) = path.iter().find(|directory| !valid_directory(directory)) {
+            return Err(SearchPathError::Invalid(invalid.clone()));
+        }
+        let joined = env::join_paths(&path).expect("search path entries are validated");
+        let joined_c = CString::new(joined.to_str().expect("search path entries are validated"))
+            .expect("search path entries are validated");
+        squash_set_default_search_path(joined_c.as_ptr());
+        if plugins.is_some() {
+            env::set_var(PLUGINS_VAR, &joined);
+        }
+        search_path.directories = Some(path);
+        Ok(())
+    }
+
+    /// Access the raw `SquashContext` pointer
+    pub fn as_ptr(self) -> *mut SquashContext {
+        self.0.as_ptr()
+    }
+
+    /// Find a plugin by name
+    pub fn plugin(self, name: &str) -> Option<Plugin> {
+        let name = CString::new(name).ok()?;
+        unsafe { Plugin::from_raw(squash_context_get_plugin(self.as_ptr(), name.as_ptr())) }
+    }
+
+    /// Find a codec by name
+    pub fn codec(self, name: &str) -> Option<Codec> {
+        let name = CString::new(name).ok()?;
+        unsafe { Codec::from_raw(squash_context_get_codec(self.as_ptr(), name.as_ptr())) }
+    }
+
+    /// Find a codec by the file extension it uses (without the leading `.`)
+    pub fn codec_from_extension(self, extension: &str) -> Option<Codec> {
+        let extension = CString::new(extension).ok()?;
+        unsafe {
+            Codec::from_raw(squash_context_get_codec_from_extension(
+                self.as_ptr(),
+                extension.as_ptr(),
+            ))
+        }
+    }
+
+    /// All the plugins found in the search path
+    ///
+    /// Plugins are listed whether or not their library can be loaded: see [`Plugin::init`].
+    pub fn plugins(self) -> impl Iterator<Item = Plugin> {
+        let mut plugins = Vec::new();
+        unsafe {
+            squash_context_foreach_plugin(
+                self.as_ptr(),
+                Some(push_plugin),
+                &mut plugins as *mut Vec<Plugin> as *mut c_void,
+            );
+        }
+        plugins.into_iter()
+    }
+
+    /// All the codecs provided by the plugins found in the search path
+    pub fn codecs(self) -> impl Iterator<Item = Codec> {
+        let mut codecs = Vec::new();
+        unsafe {
+            squash_context_foreach_codec(
+                self.as_ptr(),
+                Some(push_codec),
+                &mut codecs as *mut Vec<Codec> as *mut c_void,
+            );
+        }
+        codecs.into_iter()
+    }
+
+    /// Report on every plugin found in the search path, trying to load each of them
+    ///
+    /// This explains why a codec cannot be used on a given host: a plugin whose library, or one
+    /// of its dependencies, is missing is still listed, with the error squash returned when
+    /// initialising it.
+    pub fn diagnose(self) -> Vec<PluginDiagnosis> {
+        let directories = searched_directories();
+        self.plugins()
+            .map(|plugin| {
+                let name = plugin.name();
+                PluginDiagnosis {
+                    plugin,
+                    name,
+                    directory: directories
+                        .iter()
+                        .map(|directory| directory.join(name))
+                        .find(|directory| directory.join("squash.ini").is_file()),
+                    status: plugin.init(),
+                    codecs: plugin.codecs().collect(),
+                    licenses: plugin.licenses(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The directories searched for plugins, as far as they are known
+///
+/// Squash's built-in plugin directory is only known if pkg-config reported it when this crate
+/// was built.
+fn searched_directories() -> Vec<PathBuf> {
+    if let Some(directories) = &search_path().directories {
+        return directories.clone();
+    }
+    match env::var_os(PLUGINS_VAR) {
+        Some(path) => env::split_paths(&path).collect(),
+        None => builtin_directory().into_iter().collect(),
+    }
+}
+
+/// The state of a plugin, as reported by [`Context::diagnose`]
+#[derive(Debug, Clone)]
+pub struct PluginDiagnosis {
+    /// The plugin
+    pub plugin: Plugin,
+    /// The name of the plugin
+    pub name: &'static str,
+    /// The directory the plugin was found in, if it is in a known part of the search path
+    pub directory: Option<PathBuf>,
+    /// The result of loading the plugin's library
+    pub status: SquashResult<()>,
+    /// The codecs the plugin provides, which cannot be used if it failed to load
+    pub codecs: Vec<Codec>,
+    /// The names of the licenses the plugin is distributed under
+    pub licenses: Vec<&'static str>,
+}
+
+impl fmt::Display for PluginDiagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        if let Some(directory) = &self.directory {
+            write!(f, " ({})", directory.display())?;
+        }
+        match &self.status {
+            Ok(()) => f.write_str(": loaded")?,
+            Err(error) => write!(f, ": failed to load: {}", error)?,
+        }
+        let codecs: Vec<&str> = self.codecs.iter().map(|codec| codec.name()).collect();
+        write!(f, "; codecs: {}", codecs.join(", "))?;
+        write!(f, "; licenses: {}", self.licenses.join(", "))
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::get_default()
+    }
+}
+
+/// A `SquashPluginForeachFunc` collecting plugins into the `Vec<Plugin>` pointed to by `data`
+unsafe extern "C" fn push_plugin(plugin: *mut SquashPlugin, data: *mut c_void) {
+    let plugins = &mut *(data as *mut Vec<Plugin>);
+    plugins.extend(Plugin::from_raw(plugin));
+}
+
+/// The directory squash searches for plugins when `SQUASH_PLUGINS` is unset, as reported by
+/// pkg-config when this crate was built
+fn builtin_directory() -> Option<PathBuf> {
+    option_env!("SQUASH_BUILTIN_PLUGIN_DIR")
+        .map(PathBuf::from)
+        .filter(|directory| directory.is_dir())
+}
+
+/// Whether `directory` can be passed to squash as part of a search path
+fn valid_directory(directory: &Path) -> bool {
+    directory
+        .to_str()
+        .is_some_and(|directory| !directory.contains('\0'))
+        && env::join_paths(Some(directory)).is_ok()
+}
+
+/// The error returned by [`Context::set_search_path`]
+#[derive(Debug)]
+pub enum SearchPathError {
+    /// The directory does not exist
+    NotFound(PathBuf),
+    /// The directory cannot be passed to squash, because it is not valid Unicode or contains
+    /// the search path separator
+    Invalid(PathBuf),
+    /// The default context has already been created, and would ignore the new search path
+    ContextCreated,
+}
+
+impl fmt::Display for SearchPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchPathError::NotFound(directory) => {
+                write!(f, "plugin directory {} does not exist", directory.display())
+            }
+            SearchPathError::Invalid(directory) => write!(
+                f,
+                "plugin directory {} cannot be part of a search path",
+                directory.display()
+            ),
+            SearchPathError::ContextCreated => {
+                f.write_str("the default squash context has already been created")
+            }
+        }
+    }
+}
+
+impl error::Error for SearchPathError {}
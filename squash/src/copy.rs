@@ -0,0 +1,87 @@
+use std::io::{self, Read, Write};
+
+use crate::{Codec, Options, StreamType};
+
+/// The number of bytes read and written by a copy
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Copied {
+    /// The number of bytes read from the reader
+    pub read: u64,
+    /// The number of bytes written to the writer
+    pub written: u64,
+}
+
+/// Compress or decompress everything from `reader` into `writer`
+///
+/// Like [`std::io::copy`], but passing the data through `codec`. This uses
+/// [`Codec::splice`], so codecs with a native splice implementation use it directly.
+///
+/// If `limit` is given, at most `limit` bytes of uncompressed data are processed (read from
+/// `reader` when compressing, written to `writer` when decompressing). Squash reads its input
+/// in blocks, so more than that may be taken from `reader`: the excess is counted in
+/// [`Copied::read`] but discarded, and `reader` is left past the point where processing
+/// stopped.
+pub fn copy<R, W>(
+    codec: Codec,
+    stream_type: StreamType,
+    reader: &mut R,
+    writer: &mut W,
+    limit: Option<usize>,
+    options: Option<&Options>,
+) -> io::Result<Copied>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut copied = Copied::default();
+    let read = &mut copied.read;
+    let written = &mut copied.written;
+    codec.splice(
+        stream_type,
+        |buf| {
+            let len = reader.read(buf)?;
+            *read += len as u64;
+            Ok(len)
+        },
+        |data| {
+            writer.write_all(data)?;
+            *written += data.len() as u64;
+            Ok(())
+        },
+        limit,
+        options,
+    )?;
+    Ok(copied)
+}
+
+/// Compress everything from `reader` into `writer`
+///
+/// See [`copy`] for details.
+pub fn copy_compress<R, W>(
+    codec: Codec,
+    reader: &mut R,
+    writer: &mut W,
+    options: Option<&Options>,
+) -> io::Result<Copied>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    copy(codec, StreamType::Compress, reader, writer, None, options)
+}
+
+/// Decompress everything from `reader` into `writer`
+///
+/// See [`copy`] for details.
+pub fn copy_decompress<R, W>(
+    codec: Codec,
+    reader: &mut R,
+    writer: &mut W,
+    options: Option<&Options>,
+) -> io::Result<Copied>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    copy(codec, StreamType::Decompress, reader, writer, None, options)
+}
@@ -0,0 +1,155 @@
+use std::ffi::CStr;
+use std::ptr::NonNull;
+use std::{error, fmt, io, result};
+
+use squash_sys::{squash_status_to_string, SquashStatus};
+
+use crate::alloc::{take_allocation_failure, take_limit_failure, MemoryLimitExceeded};
+
+/// A specialized `Result` type for squash operations
+pub type Result<T> = result::Result<T, Error>;
+
+/// A successful status returned by squash
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// The operation completed successfully
+    Ok,
+    /// The operation must be called again to make more progress
+    Processing,
+    /// The end of the compressed stream was reached
+    EndOfStream,
+}
+
+/// An error status returned by squash
+///
+/// When squash ran out of memory because an allocation was refused by a
+/// [memory limit](crate::alloc::with_memory_limit), the error also records that limit: see
+/// [`Error::memory_limit_exceeded`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Error {
+    status: SquashStatus::Type,
+    memory_limit: Option<MemoryLimitExceeded>,
+}
+
+impl Error {
+    /// Create an error from a raw (negative) squash status
+    pub fn from_raw(status: SquashStatus::Type) -> Self {
+        debug_assert!(status < 0, "{} is not an error status", status);
+        Error {
+            status,
+            memory_limit: None,
+        }
+    }
+
+    /// The raw squash status
+    pub fn status(&self) -> SquashStatus::Type {
+        self.status
+    }
+
+    /// The memory limit which caused this `SQUASH_MEMORY` error, if it was caused by one
+    pub fn memory_limit_exceeded(&self) -> Option<MemoryLimitExceeded> {
+        self.memory_limit
+    }
+
+    /// A description of the error, as provided by squash
+    pub fn description(&self) -> &'static str {
+        let description = unsafe { squash_status_to_string(self.status) };
+        if description.is_null() {
+            return "unknown error";
+        }
+        unsafe { CStr::from_ptr(description) }
+            .to_str()
+            .unwrap_or("unknown error")
+    }
+
+    fn io_kind(&self) -> io::ErrorKind {
+        match self.status {
+            SquashStatus::SQUASH_MEMORY => io::ErrorKind::OutOfMemory,
+            SquashStatus::SQUASH_BAD_PARAM
+            | SquashStatus::SQUASH_BAD_VALUE
+            | SquashStatus::SQUASH_RANGE => io::ErrorKind::InvalidInput,
+            SquashStatus::SQUASH_INVALID_BUFFER => io::ErrorKind::InvalidData,
+            SquashStatus::SQUASH_BUFFER_EMPTY => io::ErrorKind::UnexpectedEof,
+            SquashStatus::SQUASH_NOT_FOUND | SquashStatus::SQUASH_UNABLE_TO_LOAD => {
+                io::ErrorKind::NotFound
+            }
+            SquashStatus::SQUASH_INVALID_OPERATION => io::ErrorKind::Unsupported,
+            _ => io::ErrorKind::Other,
+        }
+    }
+}
+
+/// Convert a raw squash status into a `Result`
+///
+/// This must be called straight after the squash call returning `status`, on the same thread,
+/// to pick up a memory limit refusing one of its allocations.
+pub(crate) fn check(status: SquashStatus::Type) -> Result<Status> {
+    // Always take the failures, so they can't be blamed for a later, unrelated error
+    take_allocation_failure();
+    let memory_limit = take_limit_failure();
+    match status {
+        SquashStatus::SQUASH_OK => Ok(Status::Ok),
+        SquashStatus::SQUASH_PROCESSING => Ok(Status::Processing),
+        SquashStatus::SQUASH_END_OF_STREAM => Ok(Status::EndOfStream),
+        SquashStatus::SQUASH_MEMORY => Err(Error {
+            status,
+            memory_limit,
+        }),
+        _ => Err(Error::from_raw(status)),
+    }
+}
+
+/// Convert the object returned by a squash constructor into a `Result`
+///
+/// Squash doesn't say why a constructor returned null: it is reported as `SQUASH_MEMORY` if an
+/// allocation failed meanwhile, blaming the memory limit which refused it if any, and as
+/// `SQUASH_FAILED` otherwise. As for [`check`], this must be called straight after the
+/// constructor, on the same thread.
+pub(crate) fn check_ptr<T>(ptr: *mut T) -> Result<NonNull<T>> {
+    if take_allocation_failure() && ptr.is_null() {
+        return Err(out_of_memory());
+    }
+    take_limit_failure();
+    NonNull::new(ptr).ok_or_else(|| Error::from_raw(SquashStatus::SQUASH_FAILED))
+}
+
+/// The error for a failed allocation, blaming a memory limit if one refused it
+pub(crate) fn out_of_memory() -> Error {
+    match check(SquashStatus::SQUASH_MEMORY) {
+        Err(err) => err,
+        Ok(_) => unreachable!(),
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Error")
+            .field("status", &self.status)
+            .field("description", &self.description())
+            .field("memory_limit", &self.memory_limit)
+            .finish()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.memory_limit {
+            Some(memory_limit) => write!(f, "{}: {}", self.description(), memory_limit),
+            None => f.write_str(self.description()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.memory_limit
+            .as_ref()
+            .map(|memory_limit| memory_limit as &(dyn error::Error + 'static))
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        io::Error::new(err.io_kind(), err)
+    }
+}
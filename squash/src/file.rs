@@ -0,0 +1,304 @@
+use std::ffi::CString;
+#[cfg(unix)]
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+#[cfg(unix)]
+use std::os::unix::io::{IntoRawFd, RawFd};
+use std::path::Path;
+use std::ptr::{self, NonNull};
+use std::{fmt, mem};
+
+use squash_sys::*;
+
+#[cfg(unix)]
+use crate::cfile::CFile;
+use crate::error::{check, check_ptr, Error};
+use crate::options::{options_ptr, Options};
+use crate::Codec;
+
+/// A file which is transparently compressed or decompressed as it is written or read
+///
+/// The file is closed when dropped, but any errors are ignored: call [`CompressedFile::close`]
+/// to handle them.
+///
+/// Like [`File`](std::fs::File), `&CompressedFile` implements `Read` and `Write`, so a file
+/// can be shared between threads. Each operation takes squash's internal lock on the file; use
+/// [`CompressedFile::lock`] to hold the lock across several operations.
+pub struct CompressedFile(NonNull<SquashFile>);
+
+// A SquashFile isn't tied to the thread which opened it, and every operation through a shared
+// reference takes its internal lock
+unsafe impl Send for CompressedFile {}
+unsafe impl Sync for CompressedFile {}
+
+impl CompressedFile {
+    /// Open the file at `path` with the `fopen`-style `mode` (e.g. `"rb"` or `"wb"`)
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        mode: &str,
+        codec: Codec,
+        options: Option<&Options>,
+    ) -> io::Result<Self> {
+        let path = path_to_cstring(path.as_ref())?;
+        let mode = CString::new(mode)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid mode"))?;
+        let file = unsafe {
+            squash_file_open_with_options(
+                codec.as_ptr(),
+                path.as_ptr(),
+                mode.as_ptr(),
+                options_ptr(options),
+            )
+        };
+        match check_ptr(file) {
+            Ok(file) => Ok(CompressedFile(file)),
+            Err(err) if err.status() == SquashStatus::SQUASH_MEMORY => Err(err.into()),
+            Err(_) => Err(open_error()),
+        }
+    }
+
+    /// Compress or decompress through an open file
+    ///
+    /// The file is read or written depending on its access mode, starting from its current
+    /// position. Use [`CompressedFile::into_inner`] to get it back.
+    #[cfg(unix)]
+    pub fn from_file(file: File, codec: Codec, options: Option<&Options>) -> io::Result<Self> {
+        unsafe { Self::from_raw_fd(file.into_raw_fd(), codec, options) }
+    }
+
+    /// Compress or decompress through an open file descriptor, like a pipe or socket
+    ///
+    /// # Safety
+    /// `fd` must be an open file descriptor, and ownership of it is transferred to the
+    /// returned file: it is closed when the file is, or if this fails.
+    #[cfg(unix)]
+    pub unsafe fn from_raw_fd(
+        fd: RawFd,
+        codec: Codec,
+        options: Option<&Options>,
+    ) -> io::Result<Self> {
+        let fp = CFile::from_raw_fd_any(fd)?;
+        let file =
+            squash_file_steal_with_options(codec.as_ptr(), fp.as_ptr(), options_ptr(options));
+        let file = check_ptr(file)?;
+        fp.into_raw();
+        Ok(CompressedFile(file))
+    }
+
+    /// Finish the compressed stream if writing, and return the underlying file
+    ///
+    /// All data is flushed to the file first. The returned file uses a duplicate of the
+    /// original descriptor, sharing its offset.
+    #[cfg(unix)]
+    pub fn into_inner(self) -> io::Result<File> {
+        let file = self.as_ptr();
+        mem::forget(self);
+        let mut fp = ptr::null_mut();
+        let status = unsafe { squash_file_free(file, &mut fp) };
+        // Take ownership of the stream before checking the status, so it's closed on failure
+        let fp = unsafe { CFile::from_raw(fp) };
+        file_result(status)?;
+        fp.ok_or_else(|| Error::from_raw(SquashStatus::SQUASH_FAILED))?
+            .into_file()
+    }
+
+    /// Whether the end of the file has been reached
+    pub fn eof(&self) -> bool {
+        unsafe { squash_file_eof(self.as_ptr()) }
+    }
+
+    /// The last error which occurred while reading or writing the file, if any
+    pub fn error(&self) -> Option<Error> {
+        check(unsafe { squash_file_error(self.as_ptr()) }).err()
+    }
+
+    /// Lock the file, returning a guard which reads and writes without further locking
+    ///
+    /// Other threads block when using the file until the guard is dropped. Don't use the file
+    /// directly from the thread holding the guard: use the guard instead.
+    pub fn lock(&self) -> FileGuard<'_> {
+        unsafe { squash_file_lock(self.as_ptr()) };
+        FileGuard {
+            file: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Close the file, finishing the compressed stream if writing
+    pub fn close(self) -> io::Result<()> {
+        let file = self.as_ptr();
+        mem::forget(self);
+        file_result(unsafe { squash_file_close(file) })
+    }
+
+    /// Access the raw `SquashFile` pointer
+    ///
+    /// The pointer is valid for as long as `self` is.
+    pub fn as_ptr(&self) -> *mut SquashFile {
+        self.0.as_ptr()
+    }
+}
+
+#[cfg(unix)]
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a nul byte"))
+}
+
+#[cfg(not(unix))]
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    path.to_str()
+        .and_then(|path| CString::new(path).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid path"))
+}
+
+/// The error for a file which squash failed to open
+fn open_error() -> io::Error {
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(0) | None => Error::from_raw(SquashStatus::SQUASH_FAILED).into(),
+        Some(_) => err,
+    }
+}
+
+/// Convert the status of a file operation into an `io::Result`
+///
+/// `SQUASH_IO` means an operation on the underlying file failed, so the OS error is more
+/// useful than squash's.
+fn file_result(status: SquashStatus::Type) -> io::Result<()> {
+    match check(status) {
+        Ok(_) => Ok(()),
+        Err(err) if err.status() == SquashStatus::SQUASH_IO => {
+            let os_err = io::Error::last_os_error();
+            match os_err.raw_os_error() {
+                Some(0) | None => Err(err.into()),
+                Some(_) => Err(os_err),
+            }
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+impl Read for CompressedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Read for &CompressedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = buf.len();
+        file_result(unsafe { squash_file_read(self.as_ptr(), &mut len, buf.as_mut_ptr()) })?;
+        Ok(len)
+    }
+}
+
+impl Write for CompressedFile {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        (&*self).write(data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+impl Write for &CompressedFile {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        file_result(unsafe { squash_file_write(self.as_ptr(), data.len(), data.as_ptr()) })?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        file_result(unsafe { squash_file_flush(self.as_ptr()) })
+    }
+}
+
+/// Formatted text is compressed as it is written, without building an intermediate `String`
+///
+/// `fmt::Error` carries no details: on failure, see [`CompressedFile::error`].
+impl fmt::Write for CompressedFile {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        fmt::Write::write_str(&mut &*self, s)
+    }
+}
+
+impl fmt::Write for &CompressedFile {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// Exclusive access to a [`CompressedFile`] shared between threads
+///
+/// Created by [`CompressedFile::lock`]. While the guard is alive, other threads block when
+/// reading, writing or flushing the file, so a batch of operations through the guard isn't
+/// interleaved with theirs. The file's lock is released when the guard is dropped.
+pub struct FileGuard<'a> {
+    file: &'a CompressedFile,
+    // The lock must be released by the thread which took it
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Read for FileGuard<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = buf.len();
+        file_result(unsafe {
+            squash_file_read_unlocked(self.file.as_ptr(), &mut len, buf.as_mut_ptr())
+        })?;
+        Ok(len)
+    }
+}
+
+impl Write for FileGuard<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        file_result(unsafe {
+            squash_file_write_unlocked(self.file.as_ptr(), data.len(), data.as_ptr())
+        })?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        file_result(unsafe { squash_file_flush_unlocked(self.file.as_ptr()) })
+    }
+}
+
+impl fmt::Write for FileGuard<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+impl fmt::Debug for FileGuard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileGuard").finish()
+    }
+}
+
+impl Drop for FileGuard<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            squash_file_unlock(self.file.as_ptr());
+        }
+    }
+}
+
+impl fmt::Debug for CompressedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressedFile")
+            .field("eof", &self.eof())
+            .field("error", &self.error())
+            .finish()
+    }
+}
+
+impl Drop for CompressedFile {
+    fn drop(&mut self) {
+        unsafe {
+            squash_file_close(self.as_ptr());
+        }
+    }
+}
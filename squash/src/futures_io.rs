@@ -0,0 +1,82 @@
+//! Async encoders and decoders for the [`futures-io`](https://docs.rs/futures-io) traits
+//!
+//! These work with any executor, e.g. smol or async-std, and behave like the adapters in the
+//! `tokio` module. An async encoder can't finish its stream when dropped: call
+//! [`AsyncWriteExt::close`] when done writing.
+//!
+//! [`AsyncWriteExt::close`]: https://docs.rs/futures/0.3/futures/io/trait.AsyncWriteExt.html#method.close
+
+use std::io::{self, IoSlice};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use ::futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+use crate::async_io::{async_adapters, DecoderCore, EncoderCore, PollBufRead, PollWrite};
+use crate::{Codec, Options};
+
+async_adapters! {
+    poll_shutdown: poll_close,
+    finished: "closed",
+    Encoder
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Encoder<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.core.poll_write(cx, &mut this.inner, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.core.poll_write_vectored(cx, &mut this.inner, bufs)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.core.poll_flush(cx, &mut this.inner)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.core.poll_shutdown(cx, &mut this.inner)
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for Decoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let data = match this.core.poll_fill_buf(cx, &mut this.inner) {
+            Poll::Ready(Ok(data)) => data,
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        this.core.consume(len);
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for Decoder<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        this.core.poll_fill_buf(cx, &mut this.inner)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().core.consume(amt);
+    }
+}
@@ -0,0 +1,59 @@
+//! Safe bindings to the [squash](http://quixdb.github.io/squash/) compression library
+//!
+//! The raw bindings are available in the [`squash_sys`] crate, re-exported as [`sys`].
+
+pub use squash_sys as sys;
+
+pub mod alloc;
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_io;
+mod buf;
+#[cfg(feature = "bytes-stream")]
+pub mod bytes_stream;
+#[cfg(unix)]
+mod cfile;
+mod codec;
+mod context;
+mod copy;
+mod error;
+mod file;
+#[cfg(feature = "futures-io")]
+pub mod futures_io;
+mod options;
+mod plugin;
+mod pool;
+pub mod read;
+mod splice;
+mod stream;
+pub mod testing;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+mod totals;
+pub mod write;
+
+pub use crate::buf::{AlignedBuf, SquashBox};
+pub use crate::codec::Codec;
+pub use crate::context::{Context, PluginDiagnosis, SearchPathError};
+pub use crate::copy::{copy, copy_compress, copy_decompress, Copied};
+pub use crate::error::{Error, Result, Status};
+pub use crate::file::{CompressedFile, FileGuard};
+pub use crate::options::{Options, OptionsRef};
+pub use crate::plugin::Plugin;
+pub use crate::pool::{PoolStats, PooledStream, StreamPool};
+#[cfg(unix)]
+pub use crate::splice::splice_files;
+pub use crate::stream::{Progress, Stream, StreamType};
+pub use crate::totals::Totals;
+
+use squash_sys::{squash_object_ref_sink, SquashObject};
+use std::os::raw::c_void;
+
+/// Take ownership of a newly created squash object
+///
+/// Objects may be created with a floating reference, which would be stolen by the first object
+/// to reference them: sink it so the returned reference is always ours.
+unsafe fn sink_object<T>(obj: *mut T) {
+    if (*(obj as *mut SquashObject)).is_floating != 0 {
+        squash_object_ref_sink(obj as *mut c_void);
+    }
+}
@@ -0,0 +1,122 @@
+use std::ffi::CString;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::os::raw::{c_char, c_void};
+use std::ptr::{self, NonNull};
+
+use squash_sys::*;
+
+use crate::error::{check, check_ptr, Error, Result};
+use crate::Codec;
+
+/// A set of options for a codec
+pub struct Options(NonNull<SquashOptions>);
+
+// Options are only modified through `&mut self`, and squash reference counts are atomic
+unsafe impl Send for Options {}
+unsafe impl Sync for Options {}
+
+impl Options {
+    /// Create a new set of options for `codec`, with every option at its default value
+    pub fn new(codec: Codec) -> Result<Self> {
+        let keys: [*const c_char; 1] = [ptr::null()];
+        let values: [*const c_char; 1] = [ptr::null()];
+        let options =
+            unsafe { squash_options_newa(codec.as_ptr(), keys.as_ptr(), values.as_ptr()) };
+        let options = check_ptr(options)?;
+        unsafe { crate::sink_object(options.as_ptr()) };
+        Ok(Options(options))
+    }
+
+    /// Parse `value` and set it as the value of the option named `key`
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let key = CString::new(key).map_err(|_| Error::from_raw(SquashStatus::SQUASH_BAD_PARAM))?;
+        let value =
+            CString::new(value).map_err(|_| Error::from_raw(SquashStatus::SQUASH_BAD_VALUE))?;
+        check(unsafe { squash_options_parse_option(self.as_ptr(), key.as_ptr(), value.as_ptr()) })?;
+        Ok(())
+    }
+
+    /// The codec these options are for
+    pub fn codec(&self) -> Codec {
+        unsafe { Codec::from_raw((*self.as_ptr()).codec) }.expect("options without a codec")
+    }
+
+    /// Access the raw `SquashOptions` pointer
+    ///
+    /// The pointer is valid for as long as `self` is.
+    pub fn as_ptr(&self) -> *mut SquashOptions {
+        self.0.as_ptr()
+    }
+
+    /// Wrap a raw options pointer, taking a new reference to it
+    pub(crate) unsafe fn from_raw_borrowed(options: *mut SquashOptions) -> Option<Self> {
+        NonNull::new(options).map(|options| {
+            squash_object_ref(options.as_ptr() as *mut c_void);
+            Options(options)
+        })
+    }
+}
+
+/// The options of a [`Stream`](crate::Stream), borrowed from the stream
+///
+/// Squash shares the options object between the stream and whoever created it, so it can only
+/// be read through this handle.
+pub struct OptionsRef<'a> {
+    options: ManuallyDrop<Options>,
+    _stream: PhantomData<&'a Options>,
+}
+
+impl OptionsRef<'_> {
+    /// Wrap a raw options pointer, without taking a reference to it
+    ///
+    /// The options must outlive the returned handle.
+    pub(crate) unsafe fn from_raw<'a>(options: *mut SquashOptions) -> Option<OptionsRef<'a>> {
+        NonNull::new(options).map(|options| OptionsRef {
+            options: ManuallyDrop::new(Options(options)),
+            _stream: PhantomData,
+        })
+    }
+
+    /// Take a new reference to the options, for creating another stream with them
+    pub(crate) fn share(&self) -> Options {
+        unsafe { Options::from_raw_borrowed(self.as_ptr()) }.unwrap()
+    }
+}
+
+impl Deref for OptionsRef<'_> {
+    type Target = Options;
+
+    fn deref(&self) -> &Options {
+        &self.options
+    }
+}
+
+impl fmt::Debug for OptionsRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Convert optional options into the pointer expected by squash
+pub(crate) fn options_ptr(options: Option<&Options>) -> *mut SquashOptions {
+    options.map_or(ptr::null_mut(), Options::as_ptr)
+}
+
+impl fmt::Debug for Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Options")
+            .field("codec", &self.codec())
+            .finish()
+    }
+}
+
+impl Drop for Options {
+    fn drop(&mut self) {
+        unsafe {
+            squash_object_unref(self.as_ptr() as *mut c_void);
+        }
+    }
+}
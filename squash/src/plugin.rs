@@ -0,0 +1,101 @@
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw::c_void;
+use std::ptr::NonNull;
+
+use squash_sys::*;
+
+use crate::codec::{static_str, Codec};
+use crate::error::{check, Result};
+
+/// A squash plugin, which provides one or more codecs
+///
+/// Plugins are owned by the default squash context, and live for the rest of the program.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Plugin(NonNull<SquashPlugin>);
+
+// Plugins are never freed, and squash serialises their initialisation
+unsafe impl Send for Plugin {}
+unsafe impl Sync for Plugin {}
+
+impl Plugin {
+    /// Wrap a raw plugin pointer
+    ///
+    /// # Safety
+    /// `plugin` must be null, or point to a plugin owned by a squash context which outlives
+    /// the program.
+    pub unsafe fn from_raw(plugin: *mut SquashPlugin) -> Option<Self> {
+        NonNull::new(plugin).map(Plugin)
+    }
+
+    /// Access the raw `SquashPlugin` pointer
+    pub fn as_ptr(self) -> *mut SquashPlugin {
+        self.0.as_ptr()
+    }
+
+    /// The name of the plugin
+    pub fn name(self) -> &'static str {
+        unsafe { static_str(squash_plugin_get_name(self.as_ptr())) }.unwrap_or("")
+    }
+
+    /// Load the plugin's library, if it is not loaded yet
+    ///
+    /// Fails if the library cannot be loaded, for example because one of its dependencies is
+    /// missing.
+    pub fn init(self) -> Result<()> {
+        check(unsafe { squash_plugin_init(self.as_ptr()) }).map(drop)
+    }
+
+    /// The names of the licenses the plugin is distributed under
+    pub fn licenses(self) -> Vec<&'static str> {
+        let mut licenses = Vec::new();
+        let mut license = unsafe { squash_plugin_get_licenses(self.as_ptr()) };
+        if license.is_null() {
+            return licenses;
+        }
+        unsafe {
+            while *license != SquashLicense::SQUASH_LICENSE_UNKNOWN {
+                licenses.extend(static_str(squash_license_to_string(*license)));
+                license = license.add(1);
+            }
+        }
+        licenses
+    }
+
+    /// Find one of the plugin's codecs by name
+    pub fn codec(self, name: &str) -> Option<Codec> {
+        let name = CString::new(name).ok()?;
+        unsafe { Codec::from_raw(squash_plugin_get_codec(self.as_ptr(), name.as_ptr())) }
+    }
+
+    /// The codecs provided by the plugin
+    pub fn codecs(self) -> impl Iterator<Item = Codec> {
+        let mut codecs = Vec::new();
+        unsafe {
+            squash_plugin_foreach_codec(
+                self.as_ptr(),
+                Some(push_codec),
+                &mut codecs as *mut Vec<Codec> as *mut c_void,
+            );
+        }
+        codecs.into_iter()
+    }
+}
+
+/// A `SquashCodecForeachFunc` collecting codecs into the `Vec<Codec>` pointed to by `data`
+pub(crate) unsafe extern "C" fn push_codec(codec: *mut SquashCodec, data: *mut c_void) {
+    let codecs = &mut *(data as *mut Vec<Codec>);
+    codecs.extend(Codec::from_raw(codec));
+}
+
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Plugin").field(&self.name()).finish()
+    }
+}
+
+impl fmt::Display for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+
+use squash_sys::*;
+
+use crate::options::{options_ptr, Options};
+use crate::{Codec, Result, Stream, StreamType};
+
+const DEFAULT_MAX_IDLE: usize = 16;
+const MAX_BUCKETS: usize = 64;
+
+/// Counters describing how well a [`StreamPool`] is working
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PoolStats {
+    /// Streams handed out from the pool, unused since they were created
+    pub hits: u64,
+    /// Streams which had to be created for a request, either because the pool had none ready
+    /// or because the stream handed out was rebuilt when it was returned
+    pub misses: u64,
+    /// Finished streams replaced by a new stream when returned
+    pub rebuilt: u64,
+    /// Returned streams which were dropped, because they were abandoned part way through or
+    /// the pool was full
+    pub discarded: u64,
+}
+
+impl PoolStats {
+    /// The fraction of requests served from the pool, or `0.0` if there were none
+    pub fn hit_rate(&self) -> f64 {
+        let requests = self.hits + self.misses;
+        if requests == 0 {
+            0.0
+        } else {
+            self.hits as f64 / requests as f64
+        }
+    }
+}
+
+/// Streams are pooled by codec, direction and options
+///
+/// Options are compared by identity: streams created with two separate but equal `Options`
+/// objects are pooled separately. Buckets only exist while they hold idle streams, which keep
+/// their options alive, so an address can't be reused while it's part of a key.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    codec: Codec,
+    stream_type: StreamType,
+    options: usize,
+}
+
+struct Idle {
+    stream: Stream,
+    rebuilt: bool,
+}
+
+struct Bucket {
+    idle: Vec<Idle>,
+    last_used: u64,
+}
+
+struct Pools {
+    buckets: HashMap<Key, Bucket>,
+    stats: PoolStats,
+    clock: u64,
+}
+
+impl Pools {
+    fn take(&mut self, key: &Key) -> Option<Idle> {
+        let bucket = self.buckets.get_mut(key)?;
+        let idle = bucket.idle.pop();
+        if bucket.idle.is_empty() {
+            self.buckets.remove(key);
+        }
+        idle
+    }
+
+    fn push(&mut self, key: Key, idle: Idle, max_idle: usize) {
+        if idle.rebuilt {
+            self.stats.rebuilt += 1;
+        }
+        if !self.buckets.contains_key(&key) && self.buckets.len() >= MAX_BUCKETS {
+            self.evict();
+        }
+        self.clock += 1;
+        let bucket = self.buckets.entry(key).or_insert_with(|| Bucket {
+            idle: Vec::new(),
+            last_used: 0,
+        });
+        if bucket.idle.len() < max_idle {
+            bucket.idle.push(idle);
+            bucket.last_used = self.clock;
+        } else {
+            // The pool filled up while the stream was rebuilt
+            self.stats.discarded += 1;
+        }
+    }
+
+    /// Drop the streams of the least recently used bucket
+    fn evict(&mut self) {
+        let oldest = self
+            .buckets
+            .iter()
+            .min_by_key(|(_, bucket)| bucket.last_used)
+            .map(|(key, _)| *key);
+        if let Some(bucket) = oldest.and_then(|key| self.buckets.remove(&key)) {
+            self.stats.discarded += bucket.idle.len() as u64;
+        }
+    }
+}
+
+/// A pool of ready to use streams, avoiding the cost of creating a stream for every message
+///
+/// [`StreamPool::get`] hands out a stream, which goes back to the pool when dropped. Streams
+/// returned before being used are reused as they are. Squash has no way to reset a stream, so
+/// streams returned in the `SQUASH_STREAM_STATE_FINISHED` state are replaced by a new stream,
+/// created by the thread dropping them. That only moves the cost of creating the stream out of
+/// [`get`](StreamPool::get), so handing out a rebuilt stream counts as a miss. Streams
+/// abandoned part way through are dropped.
+///
+/// Idle streams are kept for at most 64 combinations of codec, direction and options. Beyond
+/// that, the streams of the least recently used combination are dropped.
+///
+/// The pool can be shared between threads.
+pub struct StreamPool {
+    max_idle: usize,
+    pools: Mutex<Pools>,
+}
+
+impl StreamPool {
+    /// Create an empty pool
+    pub fn new() -> Self {
+        Self::with_max_idle(DEFAULT_MAX_IDLE)
+    }
+
+    /// Create an empty pool, keeping at most `max_idle` streams for each codec, direction and
+    /// options
+    pub fn with_max_idle(max_idle: usize) -> Self {
+        StreamPool {
+            max_idle,
+            pools: Mutex::new(Pools {
+                buckets: HashMap::new(),
+                stats: PoolStats::default(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// Take a stream from the pool, or create one if there are none ready
+    pub fn get(
+        &self,
+        codec: Codec,
+        stream_type: StreamType,
+        options: Option<&Options>,
+    ) -> Result<PooledStream<'_>> {
+        let key = Key {
+            codec,
+            stream_type,
+            options: options_ptr(options) as usize,
+        };
+        let pooled = {
+            let mut pools = self.lock();
+            let idle = pools.take(&key);
+            match idle {
+                Some(Idle { rebuilt: false, .. }) => pools.stats.hits += 1,
+                _ => pools.stats.misses += 1,
+            }
+            idle
+        };
+        let stream = match pooled {
+            Some(idle) => idle.stream,
+            None => Stream::new(codec, stream_type, options)?,
+        };
+        Ok(PooledStream {
+            pool: self,
+            key,
+            stream: Some(stream),
+        })
+    }
+
+    /// The pool's counters so far
+    pub fn stats(&self) -> PoolStats {
+        self.lock().stats
+    }
+
+    /// The number of streams ready to be handed out
+    pub fn idle(&self) -> usize {
+        let pools = self.lock();
+        pools.buckets.values().map(|bucket| bucket.idle.len()).sum()
+    }
+
+    /// Drop all idle streams
+    pub fn clear(&self) {
+        self.lock().buckets.clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Pools> {
+        // The pool's state is consistent between statements, so a panic can't corrupt it
+        self.pools
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn has_room(&self, key: &Key) -> bool {
+        let pools = self.lock();
+        pools.buckets.get(key).map_or(self.max_idle > 0, |bucket| {
+            bucket.idle.len() < self.max_idle
+        })
+    }
+
+    fn recycle(&self, key: Key, stream: Stream) {
+        let idle = if !self.has_room(&key) {
+            None
+        } else {
+            match stream.state() {
+                SquashStreamState::SQUASH_STREAM_STATE_IDLE => Some(Idle {
+                    stream,
+                    rebuilt: false,
+                }),
+                SquashStreamState::SQUASH_STREAM_STATE_FINISHED => {
+                    let options = stream.shared_options();
+                    drop(stream);
+                    Stream::new(key.codec, key.stream_type, options.as_ref())
+                        .ok()
+                        .map(|stream| Idle {
+                            stream,
+                            rebuilt: true,
+                        })
+                }
+                _ => None,
+            }
+        };
+
+        let mut pools = self.lock();
+        match idle {
+            Some(idle) => pools.push(key, idle, self.max_idle),
+            None => pools.stats.discarded += 1,
+        }
+    }
+}
+
+impl Default for StreamPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for StreamPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamPool")
+            .field("max_idle", &self.max_idle)
+            .field("idle", &self.idle())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+/// A stream borrowed from a [`StreamPool`], returned to the pool when dropped
+pub struct PooledStream<'a> {
+    pool: &'a StreamPool,
+    key: Key,
+    stream: Option<Stream>,
+}
+
+impl PooledStream<'_> {
+    /// Take the stream out of the pool for good
+    pub fn into_inner(mut self) -> Stream {
+        self.stream.take().unwrap()
+    }
+}
+
+impl Deref for PooledStream<'_> {
+    type Target = Stream;
+
+    fn deref(&self) -> &Stream {
+        self.stream.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledStream<'_> {
+    fn deref_mut(&mut self) -> &mut Stream {
+        self.stream.as_mut().unwrap()
+    }
+}
+
+impl fmt::Debug for PooledStream<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PooledStream").field(&**self).finish()
+    }
+}
+
+impl Drop for PooledStream<'_> {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            self.pool.recycle(self.key, stream);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Readers which decompress data as it is read
+
+use std::io::{self, BufRead, Read};
+
+use crate::totals::{progress_methods, Counter};
+use crate::{Codec, Options, Status, Stream, StreamType};
+
+/// Decompresses data read from an underlying buffered reader
+///
+/// Some formats (gzip, bzip2, xz and zstd) allow several compressed members to be concatenated,
+/// as produced by `cat a.gz b.gz > all.gz` or parallel compressors like pigz. In multi-member
+/// mode, when one member ends the decoder starts a fresh stream on the remaining input, and
+/// keeps going until the input is exhausted. Multi-member mode is on by default for codecs
+/// which [support concatenation](Codec::supports_concatenation).
+///
+/// When multi-member mode is off, decoding stops at the end of the first member, and any
+/// remaining input is left in the underlying reader.
+#[derive(Debug)]
+pub struct Decoder<R> {
+    stream: Stream,
+    inner: R,
+    multi_member: bool,
+    // The last call to `process` had more output to give
+    processing: bool,
+    done: bool,
+    counter: Counter,
+}
+
+impl<R: BufRead> Decoder<R> {
+    /// Create a new decoder decompressing data from `inner` with `codec`
+    pub fn new(codec: Codec, inner: R) -> io::Result<Self> {
+        Self::with_options(codec, inner, None)
+    }
+
+    /// Create a new decoder decompressing data from `inner` with `codec`, using `options`
+    pub fn with_options(codec: Codec, inner: R, options: Option<&Options>) -> io::Result<Self> {
+        Ok(Decoder {
+            stream: Stream::new(codec, StreamType::Decompress, options)?,
+            inner,
+            multi_member: codec.supports_concatenation(),
+            processing: false,
+            done: false,
+            counter: Counter::new(StreamType::Decompress),
+        })
+    }
+
+    /// Whether the decoder continues decoding after the end of a compressed member
+    pub fn multi_member(&self) -> bool {
+        self.multi_member
+    }
+
+    /// Set whether the decoder continues decoding after the end of a compressed member
+    pub fn set_multi_member(&mut self, multi_member: bool) {
+        self.multi_member = multi_member;
+    }
+
+    progress_methods! {
+        counter: counter,
+        total_in: "The number of compressed bytes read from the underlying reader so far",
+        total_out: "The number of decompressed bytes produced so far",
+        every: "of compressed data have been read",
+    }
+
+    /// Get a reference to the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader
+    ///
+    /// Reading directly from the underlying reader may corrupt the compressed stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consume the decoder, returning the underlying reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn end_of_member(&mut self) -> io::Result<()> {
+        if self.multi_member && !self.inner.fill_buf()?.is_empty() {
+            let options = self.stream.shared_options();
+            self.stream = Stream::new(
+                self.stream.codec(),
+                StreamType::Decompress,
+                options.as_ref(),
+            )?;
+        } else {
+            self.done = true;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while !self.done {
+            // Output held back by the stream is drained before reading more input, which might
+            // block until the output has been read
+            let (progress, eof) = if self.processing {
+                (self.stream.process(&[], buf)?, false)
+            } else {
+                let input = self.inner.fill_buf()?;
+                let eof = input.is_empty();
+                let progress = if eof {
+                    self.stream.finish(input, buf)?
+                } else {
+                    self.stream.process(input, buf)?
+                };
+                self.inner.consume(progress.read);
+                (progress, eof)
+            };
+            self.counter.record(&progress);
+            self.processing = !eof && progress.status == Status::Processing;
+
+            match progress.status {
+                Status::EndOfStream => self.end_of_member()?,
+                Status::Ok if eof => self.done = true,
+                _ => {}
+            }
+            if progress.written > 0 {
+                return Ok(progress.written);
+            }
+        }
+        Ok(0)
+    }
+}
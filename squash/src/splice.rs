@@ -0,0 +1,248 @@
+use std::any::Any;
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::io::{Seek, SeekFrom};
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::{io, slice, thread};
+
+use squash_sys::*;
+
+#[cfg(unix)]
+use crate::cfile::CFile;
+use crate::error::check;
+use crate::options::{options_ptr, Options};
+use crate::{Codec, StreamType};
+
+/// State shared with the splice callbacks through squash's `user_data` pointer
+struct Splice<R, W> {
+    reader: R,
+    writer: W,
+    // Stands in for a limit of zero, which squash reads as no limit
+    no_input: bool,
+    error: Option<io::Error>,
+    panic: Option<Box<dyn Any + Send + 'static>>,
+}
+
+impl<R, W> Splice<R, W> {
+    /// Stash an error or panic from a callback, to be surfaced once squash returns
+    fn settle<T>(&mut self, result: thread::Result<io::Result<T>>) -> Option<T> {
+        match result {
+            Ok(Ok(value)) => Some(value),
+            Ok(Err(err)) => {
+                self.error = Some(err);
+                None
+            }
+            Err(payload) => {
+                self.panic = Some(payload);
+                None
+            }
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.error.is_some() || self.panic.is_some()
+    }
+}
+
+// We can't unwind into the C code calling these functions, so panics are caught, and resumed
+// once squash returns
+
+unsafe extern "C" fn read_callback<R, W>(
+    data_size: *mut usize,
+    data: *mut u8,
+    user_data: *mut c_void,
+) -> SquashStatus::Type
+where
+    R: FnMut(&mut [u8]) -> io::Result<usize>,
+{
+    let splice = &mut *(user_data as *mut Splice<R, W>);
+    if splice.failed() {
+        return SquashStatus::SQUASH_IO;
+    }
+    if splice.no_input {
+        *data_size = 0;
+        return SquashStatus::SQUASH_END_OF_STREAM;
+    }
+    if *data_size == 0 {
+        return SquashStatus::SQUASH_OK;
+    }
+    let buf = slice::from_raw_parts_mut(data, *data_size);
+    let reader = &mut splice.reader;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| loop {
+        match reader(buf) {
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            result => return result,
+        }
+    }));
+    match splice.settle(result) {
+        Some(0) => {
+            *data_size = 0;
+            SquashStatus::SQUASH_END_OF_STREAM
+        }
+        Some(read) => {
+            *data_size = read;
+            SquashStatus::SQUASH_OK
+        }
+        None => SquashStatus::SQUASH_IO,
+    }
+}
+
+unsafe extern "C" fn write_callback<R, W>(
+    data_size: *mut usize,
+    data: *const u8,
+    user_data: *mut c_void,
+) -> SquashStatus::Type
+where
+    W: FnMut(&[u8]) -> io::Result<()>,
+{
+    let splice = &mut *(user_data as *mut Splice<R, W>);
+    if splice.failed() {
+        return SquashStatus::SQUASH_IO;
+    }
+    if *data_size == 0 {
+        return SquashStatus::SQUASH_OK;
+    }
+    let buf = slice::from_raw_parts(data, *data_size);
+    let writer = &mut splice.writer;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| writer(buf)));
+    match splice.settle(result) {
+        Some(()) => SquashStatus::SQUASH_OK,
+        None => SquashStatus::SQUASH_IO,
+    }
+}
+
+impl Codec {
+    /// Compress or decompress all data from `reader` into `writer`
+    ///
+    /// `reader` is called to fill a buffer with input, and returns the number of bytes read:
+    /// returning `0` signals the end of the input. `writer` is called with each chunk of
+    /// output, and must write all of it.
+    ///
+    /// If `limit` is given, at most `limit` bytes of uncompressed data are processed (read from
+    /// `reader` when compressing, written to `writer` when decompressing). With a limit of
+    /// zero, compressing produces a compressed empty input without calling `reader`, and
+    /// decompressing returns straight away.
+    ///
+    /// Errors returned by the callbacks are returned unchanged, and panics in the callbacks
+    /// are resumed once squash returns.
+    pub fn splice<R, W>(
+        self,
+        stream_type: StreamType,
+        reader: R,
+        writer: W,
+        limit: Option<usize>,
+        options: Option<&Options>,
+    ) -> io::Result<()>
+    where
+        R: FnMut(&mut [u8]) -> io::Result<usize>,
+        W: FnMut(&[u8]) -> io::Result<()>,
+    {
+        let (limit, no_input) = match limit {
+            Some(0) if stream_type == StreamType::Decompress => return Ok(()),
+            Some(0) => (0, true),
+            limit => (limit.unwrap_or(0), false),
+        };
+        let mut splice = Splice {
+            reader,
+            writer,
+            no_input,
+            error: None,
+            panic: None,
+        };
+        let status = unsafe {
+            squash_splice_custom_with_options(
+                self.as_ptr(),
+                stream_type.as_raw(),
+                Some(write_callback::<R, W>),
+                Some(read_callback::<R, W>),
+                &mut splice as *mut Splice<R, W> as *mut c_void,
+                limit,
+                options_ptr(options),
+            )
+        };
+        if let Some(payload) = splice.panic.take() {
+            panic::resume_unwind(payload);
+        }
+        if let Some(err) = splice.error.take() {
+            return Err(err);
+        }
+        check(status)?;
+        Ok(())
+    }
+}
+
+/// Compress or decompress the contents of `input` into `output`
+///
+/// This uses `squash_splice_with_options` on C streams opened on duplicates of the files'
+/// descriptors, which lets squash memory-map regular files: this is much faster than
+/// [`copy`](crate::copy) for large files.
+///
+/// Reading starts at `input`'s current position, and writing at `output`'s. On success, each
+/// file is left positioned just after the data squash read or wrote; on failure, both are
+/// moved back to where they started. The positions of unseekable files (like pipes) are left
+/// alone, and squash may have read past the end of the compressed data in them.
+#[cfg(unix)]
+pub fn splice_files(
+    codec: Codec,
+    stream_type: StreamType,
+    input: &File,
+    output: &File,
+    options: Option<&Options>,
+) -> io::Result<()> {
+    let input_start = { input }.stream_position().ok();
+    let output_start = { output }.stream_position().ok();
+
+    match splice_c_files(codec, stream_type, input, output, options) {
+        Ok((input_end, output_end)) => {
+            seek_if_known(input, input_end)?;
+            seek_if_known(output, output_end)?;
+            Ok(())
+        }
+        Err(err) => {
+            let _ = seek_if_known(input, input_start);
+            let _ = seek_if_known(output, output_start);
+            Err(err)
+        }
+    }
+}
+
+/// Splice between C streams on `input` and `output`, returning the final stream positions
+#[cfg(unix)]
+fn splice_c_files(
+    codec: Codec,
+    stream_type: StreamType,
+    input: &File,
+    output: &File,
+    options: Option<&Options>,
+) -> io::Result<(Option<u64>, Option<u64>)> {
+    let fp_in = CFile::dup(input, b"rb\0")?;
+    let mut fp_out = CFile::dup(output, b"wb\0")?;
+
+    check(unsafe {
+        squash_splice_with_options(
+            codec.as_ptr(),
+            stream_type.as_raw(),
+            fp_out.as_ptr(),
+            fp_in.as_ptr(),
+            0,
+            options_ptr(options),
+        )
+    })?;
+
+    fp_out.flush()?;
+    let input_end = fp_in.position().ok();
+    let output_end = fp_out.position().ok();
+    fp_out.close()?;
+    fp_in.close()?;
+    Ok((input_end, output_end))
+}
+
+#[cfg(unix)]
+fn seek_if_known(mut file: &File, position: Option<u64>) -> io::Result<()> {
+    if let Some(position) = position {
+        file.seek(SeekFrom::Start(position))?;
+    }
+    Ok(())
+}
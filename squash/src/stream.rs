@@ -0,0 +1,182 @@
+use std::fmt;
+use std::os::raw::c_void;
+use std::ptr::{self, NonNull};
+
+use squash_sys::*;
+
+use crate::error::{check, check_ptr, Result, Status};
+use crate::options::{options_ptr, Options, OptionsRef};
+use crate::Codec;
+
+/// The direction of a stream
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum StreamType {
+    /// Compress data
+    Compress,
+    /// Decompress data
+    Decompress,
+}
+
+impl StreamType {
+    /// The raw squash stream type
+    pub fn as_raw(self) -> SquashStreamType::Type {
+        match self {
+            StreamType::Compress => SquashStreamType::SQUASH_STREAM_COMPRESS,
+            StreamType::Decompress => SquashStreamType::SQUASH_STREAM_DECOMPRESS,
+        }
+    }
+}
+
+/// The progress made by a single call into a stream
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Progress {
+    /// The status squash returned
+    pub status: Status,
+    /// The number of bytes consumed from the input
+    pub read: usize,
+    /// The number of bytes written to the output
+    pub written: usize,
+}
+
+/// A compression or decompression stream
+///
+/// This is a thin wrapper around a `SquashStream`: input and output buffers are passed to each
+/// call, and the number of bytes consumed and produced is reported back in a [`Progress`].
+pub struct Stream(NonNull<SquashStream>);
+
+// Streams are only used through `&mut self`, and don't depend on the thread they were created on
+unsafe impl Send for Stream {}
+
+impl Stream {
+    /// Create a new stream for `codec`
+    pub fn new(codec: Codec, stream_type: StreamType, options: Option<&Options>) -> Result<Self> {
+        let stream = unsafe {
+            squash_codec_create_stream_with_options(
+                codec.as_ptr(),
+                stream_type.as_raw(),
+                options_ptr(options),
+            )
+        };
+        let stream = check_ptr(stream)?;
+        unsafe { crate::sink_object(stream.as_ptr()) };
+        Ok(Stream(stream))
+    }
+
+    /// The codec used by the stream
+    pub fn codec(&self) -> Codec {
+        unsafe { Codec::from_raw((*self.as_ptr()).codec) }.expect("stream without a codec")
+    }
+
+    /// Whether the stream compresses or decompresses
+    pub fn stream_type(&self) -> StreamType {
+        match unsafe { (*self.as_ptr()).stream_type } {
+            SquashStreamType::SQUASH_STREAM_COMPRESS => StreamType::Compress,
+            _ => StreamType::Decompress,
+        }
+    }
+
+    /// The options the stream was created with, if any
+    pub fn options(&self) -> Option<OptionsRef<'_>> {
+        unsafe { OptionsRef::from_raw((*self.as_ptr()).options) }
+    }
+
+    /// A new reference to the stream's options, for creating another stream with them
+    pub(crate) fn shared_options(&self) -> Option<Options> {
+        self.options().map(|options| options.share())
+    }
+
+    /// The current state of the stream
+    pub fn state(&self) -> SquashStreamState::Type {
+        unsafe { (*self.as_ptr()).state }
+    }
+
+    /// The number of bytes the stream has consumed
+    pub fn total_in(&self) -> usize {
+        unsafe { (*self.as_ptr()).total_in }
+    }
+
+    /// The number of bytes the stream has produced
+    pub fn total_out(&self) -> usize {
+        unsafe { (*self.as_ptr()).total_out }
+    }
+
+    /// Access the raw `SquashStream` pointer
+    ///
+    /// The pointer is valid for as long as `self` is.
+    pub fn as_ptr(&self) -> *mut SquashStream {
+        self.0.as_ptr()
+    }
+
+    /// Process some data
+    ///
+    /// Returns [`Status::Processing`] if the output buffer filled before all output for the
+    /// consumed input could be written, and [`Status::EndOfStream`] when a decompression stream
+    /// reaches the end of the compressed data.
+    pub fn process(&mut self, input: &[u8], output: &mut [u8]) -> Result<Progress> {
+        self.call(squash_stream_process, input, output)
+    }
+
+    /// Process `input`, then flush all buffered data to the output
+    ///
+    /// Only supported by codecs with `SQUASH_CODEC_INFO_CAN_FLUSH`.
+    pub fn flush(&mut self, input: &[u8], output: &mut [u8]) -> Result<Progress> {
+        self.call(squash_stream_flush, input, output)
+    }
+
+    /// Process `input`, then finish the stream
+    ///
+    /// Must be called again while it returns [`Status::Processing`].
+    pub fn finish(&mut self, input: &[u8], output: &mut [u8]) -> Result<Progress> {
+        self.call(squash_stream_finish, input, output)
+    }
+
+    fn call(
+        &mut self,
+        operation: unsafe extern "C" fn(*mut SquashStream) -> SquashStatus::Type,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Result<Progress> {
+        let stream = self.as_ptr();
+        unsafe {
+            (*stream).next_in = input.as_ptr();
+            (*stream).avail_in = input.len();
+            (*stream).next_out = output.as_mut_ptr();
+            (*stream).avail_out = output.len();
+
+            let status = operation(stream);
+
+            let read = input.len() - (*stream).avail_in;
+            let written = output.len() - (*stream).avail_out;
+
+            // Don't leave dangling pointers to the buffers behind
+            (*stream).next_in = ptr::null();
+            (*stream).avail_in = 0;
+            (*stream).next_out = ptr::null_mut();
+            (*stream).avail_out = 0;
+
+            check(status).map(|status| Progress {
+                status,
+                read,
+                written,
+            })
+        }
+    }
+}
+
+impl fmt::Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stream")
+            .field("codec", &self.codec())
+            .field("stream_type", &self.stream_type())
+            .field("state", &self.state())
+            .finish()
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        unsafe {
+            squash_object_unref(self.as_ptr() as *mut c_void);
+        }
+    }
+}
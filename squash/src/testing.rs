@@ -0,0 +1,199 @@
+//! Helpers for testing code which uses squash
+//!
+//! [`use_canary_allocator`] replaces squash's memory functions with ones which write a canary
+//! value (`0xBADC0FFEE0DDF00D`) before each block, and check it when the block is freed or
+//! reallocated, catching pointers which squash didn't allocate. It also counts outstanding
+//! allocations, which [`assert_no_leaks`] uses to check that every stream, options and file
+//! object is freed.
+//!
+//! The allocator can also fail allocations on purpose with [`with_faults`], and
+//! [`check_allocation_failures`] uses that to check that a codec copes with running out of
+//! memory at any point.
+//!
+//! ```no_run
+//! use squash::testing;
+//!
+//! #[test]
+//! fn compress_frees_everything() {
+//!     // Ignore the error if another test installed it first
+//!     let _ = unsafe { testing::use_canary_allocator() };
+//!     let codec = squash::Codec::find("gzip").unwrap();
+//!     testing::warm_up(codec);
+//!     testing::assert_no_leaks(|| codec.compress(b"data", None).unwrap());
+//! }
+//! ```
+
+use squash_sys::SquashStatus;
+
+use crate::alloc::{self, AlreadyInstalled};
+use crate::{Codec, Status, Stream, StreamType};
+
+/// Install the canary allocator
+///
+/// This is the allocator installed by [`use_tracking_allocator`](alloc::use_tracking_allocator),
+/// so memory limits and [`measure`](alloc::measure) work with it too. It can only be
+/// installed once per process: later calls return [`AlreadyInstalled`].
+///
+/// # Safety
+/// This must be called before squash allocates anything, as for
+/// [`use_rust_allocator`](alloc::use_rust_allocator).
+pub unsafe fn use_canary_allocator() -> Result<(), AlreadyInstalled> {
+    alloc::use_tracking_allocator()
+}
+
+/// The number of blocks squash currently has allocated, across all threads
+pub fn outstanding_allocations() -> usize {
+    alloc::blocks_in_use()
+}
+
+/// The number of times squash tried to free or reallocate a pointer without a valid canary
+pub fn canary_failures() -> usize {
+    alloc::canary_failures()
+}
+
+/// Load `codec`'s plugin, and run it once each way
+///
+/// Loading a plugin allocates memory which is only freed when the program exits, so use this
+/// before [`assert_no_leaks`].
+pub fn warm_up(codec: Codec) {
+    if let Ok(compressed) = codec.compress(b"warm up", None) {
+        let _ = codec.decompress(&compressed, None);
+    }
+}
+
+/// Run `f`, and panic if squash allocated memory on this thread which it didn't free
+///
+/// This also panics if squash was handed an invalid pointer while `f` ran, on any thread.
+/// Memory is attributed to the thread allocating or freeing it, so other threads using squash
+/// at the same time don't cause false positives. Nothing is checked unless the canary
+/// allocator is installed.
+pub fn assert_no_leaks<T, F: FnOnce() -> T>(f: F) -> T {
+    let failures = canary_failures();
+    let (result, stats) = alloc::measure(f);
+    assert_eq!(
+        canary_failures(),
+        failures,
+        "squash freed or reallocated an invalid pointer"
+    );
+    let leaked = stats.allocations.saturating_sub(stats.deallocations);
+    assert!(
+        leaked == 0 && stats.live == 0,
+        "squash leaked {} allocation(s) totalling {} bytes",
+        leaked,
+        stats.live
+    );
+    result
+}
+
+/// Which allocations [`with_faults`] fails
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Fault {
+    /// Fail the allocation with this index, counting from zero
+    Nth(u64),
+    /// Fail each allocation with this probability, picked by a random number generator
+    /// started from `seed`, so runs can be repeated
+    Random {
+        /// The probability of failing each allocation, from `0.0` to `1.0`
+        probability: f64,
+        /// The seed of the random number generator
+        seed: u64,
+    },
+}
+
+/// What happened while [`with_faults`] ran
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FaultStats {
+    /// The number of allocations attempted, including failed ones
+    pub allocations: u64,
+    /// The number of allocations which were failed on purpose
+    pub injected: u64,
+}
+
+/// Run `f`, failing the allocations squash makes on this thread as described by `fault`
+///
+/// Failed allocations return null, as if the system was out of memory, so squash reports
+/// `SQUASH_MEMORY` errors. Reallocations count as allocations. Nothing is failed unless squash
+/// allocates through Rust, with [`use_rust_allocator`](alloc::use_rust_allocator) or
+/// [`use_canary_allocator`].
+pub fn with_faults<T, F: FnOnce() -> T>(fault: Fault, f: F) -> (T, FaultStats) {
+    let (result, state) = alloc::with_faults(fault, f);
+    let stats = FaultStats {
+        allocations: state.allocations,
+        injected: state.injected,
+    };
+    (result, stats)
+}
+
+/// Fail each allocation of a compress and decompress round trip of `input` in turn, and check
+/// that squash reports `SQUASH_MEMORY` every time
+///
+/// The round trip is repeated with the first allocation failing, then the second, and so on
+/// until it completes without reaching the failing allocation. Each run must either return a
+/// `SQUASH_MEMORY` error or, if the codec recovered, the original input. This includes the
+/// first allocations, which create the stream: squash returns a null stream then, which is
+/// reported as `SQUASH_MEMORY` since an allocation failed. A codec which crashes instead takes
+/// the test down with it.
+///
+/// # Panics
+/// Panics if a run returns another error or the wrong output, or if squash frees an invalid
+/// pointer.
+pub fn check_allocation_failures(codec: Codec, input: &[u8]) {
+    warm_up(codec);
+    for n in 0.. {
+        let failures = canary_failures();
+        let (result, stats) = with_faults(Fault::Nth(n), || round_trip(codec, input));
+        assert_eq!(
+            canary_failures(),
+            failures,
+            "{}: squash freed an invalid pointer after allocation {} failed",
+            codec,
+            n
+        );
+        match result {
+            Ok(output) => assert!(
+                output == input,
+                "{}: wrong output after allocation {} failed",
+                codec,
+                n
+            ),
+            Err(err) => assert_eq!(
+                err.status(),
+                SquashStatus::SQUASH_MEMORY,
+                "{}: failing allocation {} returned {}",
+                codec,
+                n,
+                err
+            ),
+        }
+        if stats.injected == 0 {
+            break;
+        }
+    }
+}
+
+/// Compress `input` a piece at a time with a stream, then decompress it again
+fn round_trip(codec: Codec, input: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut stream = Stream::new(codec, StreamType::Compress, None)?;
+    let mut buf = vec![0; 4096];
+    let mut compressed = Vec::new();
+    for chunk in input.chunks(1024) {
+        let mut chunk = chunk;
+        loop {
+            let progress = stream.process(chunk, &mut buf)?;
+            compressed.extend_from_slice(&buf[..progress.written]);
+            chunk = &chunk[progress.read..];
+            if progress.status != Status::Processing && chunk.is_empty() {
+                break;
+            }
+        }
+    }
+    loop {
+        let progress = stream.finish(&[], &mut buf)?;
+        compressed.extend_from_slice(&buf[..progress.written]);
+        if progress.status != Status::Processing {
+            break;
+        }
+    }
+    drop(stream);
+    codec.decompress(&compressed, None)
+}
@@ -0,0 +1,89 @@
+//! Async encoders and decoders for [tokio](https://tokio.rs)
+//!
+//! For other runtimes, enable the `futures-io` feature and use the `futures_io` module.
+//!
+//! These drive a squash stream incrementally from `poll_*` methods, so they never block the
+//! executor. Unlike the blocking [`Encoder`](crate::write::Encoder), an async encoder can't
+//! finish its stream when dropped: call [`AsyncWriteExt::shutdown`] when done writing.
+//!
+//! [`AsyncWriteExt::shutdown`]: https://docs.rs/tokio/1/tokio/io/trait.AsyncWriteExt.html#method.shutdown
+
+use std::io::{self, IoSlice};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use ::tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::async_io::{async_adapters, DecoderCore, EncoderCore, PollBufRead, PollWrite};
+use crate::{Codec, Options};
+
+async_adapters! {
+    poll_shutdown: poll_shutdown,
+    finished: "shut down",
+    /// Note that `tokio::io::copy` flushes once its reader is exhausted.
+    Encoder
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for Encoder<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.core.poll_write(cx, &mut this.inner, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.core.poll_write_vectored(cx, &mut this.inner, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.core.poll_flush(cx, &mut this.inner)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.core.poll_shutdown(cx, &mut this.inner)
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for Decoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let data = match this.core.poll_fill_buf(cx, &mut this.inner) {
+            Poll::Ready(Ok(data)) => data,
+            Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+            Poll::Pending => return Poll::Pending,
+        };
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        this.core.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBufRead for Decoder<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        this.core.poll_fill_buf(cx, &mut this.inner)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().core.consume(amt);
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+
+use crate::{Progress, StreamType};
+
+/// The number of bytes an encoder or decoder has consumed and produced
+///
+/// Counts continue across the members of multi-member streams.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Totals {
+    /// Whether the data is being compressed or decompressed
+    pub stream_type: StreamType,
+    /// The number of bytes consumed
+    pub total_in: u64,
+    /// The number of bytes produced
+    pub total_out: u64,
+}
+
+impl Totals {
+    /// The size of the uncompressed side of the stream so far
+    pub fn uncompressed(&self) -> u64 {
+        match self.stream_type {
+            StreamType::Compress => self.total_in,
+            StreamType::Decompress => self.total_out,
+        }
+    }
+
+    /// The size of the compressed side of the stream so far
+    pub fn compressed(&self) -> u64 {
+        match self.stream_type {
+            StreamType::Compress => self.total_out,
+            StreamType::Decompress => self.total_in,
+        }
+    }
+
+    /// The compression ratio so far: the uncompressed size divided by the compressed size
+    ///
+    /// This is `0.0` until some compressed data has been consumed or produced. Compressors
+    /// buffer their input, so the ratio is only meaningful once a good amount of output has
+    /// been produced.
+    pub fn ratio(&self) -> f64 {
+        match self.compressed() {
+            0 => 0.0,
+            compressed => self.uncompressed() as f64 / compressed as f64,
+        }
+    }
+}
+
+/// Define `total_in`, `total_out`, `ratio` and the progress callback methods of an encoder or
+/// decoder, from the path to its [`Counter`] field and the docs which differ between them
+macro_rules! progress_methods {
+    (
+        counter: $($counter:ident).+,
+        total_in: $total_in:literal,
+        total_out: $total_out:literal,
+        every: $every:literal $(,)?
+    ) => {
+        #[doc = $total_in]
+        pub fn total_in(&self) -> u64 {
+            self.$($counter).+.totals().total_in
+        }
+
+        #[doc = $total_out]
+        pub fn total_out(&self) -> u64 {
+            self.$($counter).+.totals().total_out
+        }
+
+        /// The compression ratio so far, as described by [`Totals::ratio`]
+        ///
+        /// [`Totals::ratio`]: crate::Totals::ratio
+        pub fn ratio(&self) -> f64 {
+            self.$($counter).+.totals().ratio()
+        }
+
+        /// Call `callback` with the totals each time another `every` bytes
+        #[doc = $every]
+        ///
+        /// # Panics
+        /// Panics if `every` is zero.
+        pub fn set_progress_callback<F>(&mut self, every: u64, callback: F)
+        where
+            F: FnMut(&crate::Totals) + Send + 'static,
+        {
+            self.$($counter).+.set_callback(every, callback);
+        }
+
+        /// Stop calling the progress callback
+        pub fn clear_progress_callback(&mut self) {
+            self.$($counter).+.clear_callback();
+        }
+    };
+}
+
+pub(crate) use progress_methods;
+
+struct Callback {
+    interval: u64,
+    next: u64,
+    report: Box<dyn FnMut(&Totals) + Send>,
+}
+
+/// Running totals for an encoder or decoder, with an optional progress callback
+pub(crate) struct Counter {
+    totals: Totals,
+    callback: Option<Callback>,
+}
+
+impl Counter {
+    pub(crate) fn new(stream_type: StreamType) -> Self {
+        Counter {
+            totals: Totals {
+                stream_type,
+                total_in: 0,
+                total_out: 0,
+            },
+            callback: None,
+        }
+    }
+
+    pub(crate) fn totals(&self) -> Totals {
+        self.totals
+    }
+
+    /// Call `report` each time another `interval` bytes of input have been consumed
+    pub(crate) fn set_callback<F>(&mut self, interval: u64, report: F)
+    where
+        F: FnMut(&Totals) + Send + 'static,
+    {
+        assert!(interval > 0, "progress interval must be non-zero");
+        self.callback = Some(Callback {
+            interval,
+            next: (self.totals.total_in / interval + 1) * interval,
+            report: Box::new(report),
+        });
+    }
+
+    pub(crate) fn clear_callback(&mut self) {
+        self.callback = None;
+    }
+
+    pub(crate) fn record(&mut self, progress: &Progress) {
+        self.totals.total_in += progress.read as u64;
+        self.totals.total_out += progress.written as u64;
+        if let Some(callback) = &mut self.callback {
+            if self.totals.total_in >= callback.next {
+                (callback.report)(&self.totals);
+                callback.next = (self.totals.total_in / callback.interval + 1) * callback.interval;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Counter")
+            .field("totals", &self.totals)
+            .field("interval", &self.callback.as_ref().map(|c| c.interval))
+            .finish()
+    }
+}
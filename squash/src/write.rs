@@ -0,0 +1,228 @@
+//! Writers which compress data as it is written
+
+use std::fmt;
+use std::io::{self, IoSlice, Write};
+
+use crate::totals::{progress_methods, Counter};
+use crate::{Codec, Options, Progress, Status, Stream, StreamType};
+
+const BUFFER_SIZE: usize = 32 * 1024;
+
+/// Compresses data written to it, and writes the compressed data to an underlying writer
+///
+/// The stream is finished when the encoder is dropped, but any errors are ignored: call
+/// [`Encoder::finish`] to handle them.
+///
+/// Flushing the encoder pushes out all data written so far with `squash_stream_flush`. Codecs
+/// without `SQUASH_CODEC_INFO_CAN_FLUSH` return an [`Unsupported`](io::ErrorKind::Unsupported)
+/// error from [`flush`](Write::flush), unless [restarting on flush](Encoder::set_restart_on_flush)
+/// is enabled.
+#[derive(Debug)]
+pub struct Encoder<W: Write> {
+    stream: Stream,
+    inner: Option<W>,
+    buf: Vec<u8>,
+    // Compressed output in `buf[pos..len]` not yet accepted by the underlying writer
+    pos: usize,
+    len: usize,
+    finished: bool,
+    restart_on_flush: bool,
+    pending: bool,
+    counter: Counter,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Create a new encoder compressing with `codec` into `inner`
+    pub fn new(codec: Codec, inner: W) -> io::Result<Self> {
+        Self::with_options(codec, inner, None)
+    }
+
+    /// Create a new encoder compressing with `codec` into `inner`, using `options`
+    pub fn with_options(codec: Codec, inner: W, options: Option<&Options>) -> io::Result<Self> {
+        Ok(Encoder {
+            stream: Stream::new(codec, StreamType::Compress, options)?,
+            inner: Some(inner),
+            buf: vec![0; BUFFER_SIZE],
+            pos: 0,
+            len: 0,
+            finished: false,
+            restart_on_flush: false,
+            pending: false,
+            counter: Counter::new(StreamType::Compress),
+        })
+    }
+
+    /// Whether flushing a codec which can't flush ends the current member and starts a new one
+    pub fn restart_on_flush(&self) -> bool {
+        self.restart_on_flush
+    }
+
+    /// Set whether flushing a codec which can't flush ends the current member and starts a
+    /// new one
+    ///
+    /// The output is then a sequence of complete compressed members, which can be decoded by a
+    /// [multi-member](crate::read::Decoder::set_multi_member) decoder when the codec
+    /// [supports concatenation](Codec::supports_concatenation).
+    pub fn set_restart_on_flush(&mut self, restart_on_flush: bool) {
+        self.restart_on_flush = restart_on_flush;
+    }
+
+    progress_methods! {
+        counter: counter,
+        total_in: "The number of bytes written to the encoder so far",
+        total_out: "The number of compressed bytes written to the underlying writer so far",
+        every: "have been written to the encoder",
+    }
+
+    /// Get a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Get a mutable reference to the underlying writer
+    ///
+    /// Writing directly to the underlying writer may corrupt the compressed stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Finish the compressed stream, without consuming the encoder
+    ///
+    /// No more data may be written after the stream is finished. If writing to the underlying
+    /// writer fails, the compressed data is kept and the call can be retried.
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        while !self.finished {
+            self.dump()?;
+            let progress = self.stream.finish(&[], &mut self.buf)?;
+            self.fill(&progress);
+            self.finished = progress.status != Status::Processing;
+        }
+        self.dump()
+    }
+
+    /// Flush the stream with `squash_stream_flush`, or restart it if the codec can't flush
+    fn flush_stream(&mut self) -> io::Result<()> {
+        let codec = self.stream.codec();
+        if codec.can_flush() {
+            loop {
+                self.dump()?;
+                let progress = self.stream.flush(&[], &mut self.buf)?;
+                self.fill(&progress);
+                if progress.status != Status::Processing {
+                    break;
+                }
+            }
+            self.dump()?;
+        } else if self.restart_on_flush {
+            if !self.pending {
+                return Ok(());
+            }
+            self.try_finish()?;
+            let options = self.stream.shared_options();
+            self.stream = Stream::new(codec, StreamType::Compress, options.as_ref())?;
+            self.finished = false;
+        } else {
+            return Err(flush_unsupported(codec));
+        }
+        self.pending = false;
+        Ok(())
+    }
+
+    /// Finish the compressed stream, and return the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    /// Take the output of a stream operation into the buffer, once the buffer has been dumped
+    fn fill(&mut self, progress: &Progress) {
+        self.counter.record(progress);
+        self.pos = 0;
+        self.len = progress.written;
+    }
+
+    /// Write the buffered output to the underlying writer
+    fn dump(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        while self.pos < self.len {
+            match inner.write(&self.buf[self.pos..self.len]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => self.pos += written,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::other("write to a finished encoder"));
+        }
+        loop {
+            self.dump()?;
+            let progress = self.stream.process(data, &mut self.buf)?;
+            self.fill(&progress);
+            self.pending |= progress.read > 0;
+            if progress.read > 0 || progress.status != Status::Processing {
+                return Ok(progress.read);
+            }
+        }
+    }
+
+    /// Each slice is fed to the stream in turn, without copying them into one buffer
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let mut written = 0;
+        for buf in bufs.iter().filter(|buf| !buf.is_empty()) {
+            match self.write(buf) {
+                Ok(len) => {
+                    written += len;
+                    if len < buf.len() {
+                        break;
+                    }
+                }
+                // The stream's output for the slices already written stays buffered, and the
+                // error comes up again on the next write
+                Err(_) if written > 0 => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.flush_stream()?;
+        }
+        self.dump()?;
+        self.get_mut().flush()
+    }
+}
+
+/// The error returned when flushing a codec which can't flush
+pub(crate) fn flush_unsupported(codec: Codec) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("the {} codec does not support flushing", codec),
+    )
+}
+
+/// Each piece of formatted text is fed to the stream as `write!` produces it, and compressed
+/// output is passed on to the underlying writer as squash produces it
+///
+/// `fmt::Error` carries no details: use [`io::Write::write_fmt`] to get the underlying error.
+impl<W: Write> fmt::Write for Encoder<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+impl<W: Write> Drop for Encoder<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use squash::sys::{squash_malloc, SquashStatus};
+use squash::{AlignedBuf, Codec, SquashBox};
+use std::ptr;
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+#[test]
+fn aligned_buf() {
+    for &align in &[16, 64, 4096] {
+        let buf = AlignedBuf::new(100, align).unwrap();
+        assert_eq!(buf.as_ptr() as usize % align, 0);
+        assert_eq!(buf.align(), align);
+        assert_eq!(buf.len(), 100);
+        assert!(buf.iter().all(|&b| b == 0));
+    }
+    assert!(AlignedBuf::new(0, 64).unwrap().is_empty());
+
+    let err = AlignedBuf::new(100, 48).unwrap_err();
+    assert_eq!(err.status(), SquashStatus::SQUASH_BAD_PARAM);
+}
+
+#[test]
+fn compress_between_aligned_bufs() {
+    let codec = Codec::find("gzip").unwrap();
+    let input = AlignedBuf::from_slice(LOREM_IPSUM, 64).unwrap();
+    let mut output = AlignedBuf::new(codec.max_compressed_size(input.len()), 64).unwrap();
+    let len = codec.compress_into(&input, &mut output, None).unwrap();
+    assert_eq!(codec.decompress(&output[..len], None).unwrap(), LOREM_IPSUM);
+}
+
+#[test]
+fn squash_box() {
+    let mut boxed = SquashBox::from_slice(LOREM_IPSUM).unwrap();
+    assert_eq!(&*boxed, LOREM_IPSUM);
+    boxed[0] = b'l';
+    assert_eq!(boxed[0], b'l');
+
+    let zeroed = SquashBox::new_zeroed(10).unwrap();
+    assert_eq!(&*zeroed, &[0; 10]);
+    assert!(SquashBox::new_zeroed(0).unwrap().is_empty());
+
+    // Memory allocated by squash is taken over without copying
+    let boxed = unsafe {
+        let ptr = squash_malloc(3) as *mut u8;
+        ptr::copy_nonoverlapping(b"abc".as_ptr(), ptr, 3);
+        SquashBox::from_raw_parts(ptr, 3).unwrap()
+    };
+    assert_eq!(&*boxed, b"abc");
+    assert!(unsafe { SquashBox::from_raw_parts(ptr::null_mut(), 0) }.is_none());
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "bytes-stream")]
+
+use bytes::Bytes;
+use futures::executor::block_on;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use squash::bytes_stream::{compress_stream, decompress_stream};
+use squash::Codec;
+use std::io;
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+fn chunks(data: &[u8], size: usize) -> impl futures::Stream<Item = io::Result<Bytes>> + Unpin {
+    let chunks: Vec<_> = data
+        .chunks(size)
+        .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+        .collect();
+    stream::iter(chunks)
+}
+
+#[test]
+fn round_trip() {
+    let codec = Codec::find("gzip").unwrap();
+    let compressed = compress_stream(codec, chunks(LOREM_IPSUM, 100)).unwrap();
+    let compressed: Vec<Bytes> = block_on(compressed.try_collect()).unwrap();
+    let compressed = compressed.concat();
+    assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+
+    let mut decompressed = decompress_stream(codec, chunks(&compressed, 7)).unwrap();
+    decompressed.set_chunk_size(64);
+    let decompressed: Vec<Bytes> = block_on(decompressed.try_collect()).unwrap();
+    let (last, full) = decompressed.split_last().unwrap();
+    assert!(full.iter().all(|chunk| chunk.len() == 64));
+    assert!(!last.is_empty() && last.len() <= 64);
+    assert_eq!(decompressed.concat(), LOREM_IPSUM);
+}
+
+#[test]
+fn empty_input() {
+    let codec = Codec::find("gzip").unwrap();
+    let compressed = compress_stream(codec, chunks(&[], 1)).unwrap();
+    let compressed: Vec<Bytes> = block_on(compressed.try_collect()).unwrap();
+    let compressed = compressed.concat();
+    assert!(!compressed.is_empty());
+    assert!(codec.decompress(&compressed, None).unwrap().is_empty());
+}
+
+#[test]
+fn input_errors_pass_through() {
+    let codec = Codec::find("gzip").unwrap();
+    let input = stream::iter(vec![
+        Ok(Bytes::from_static(LOREM_IPSUM)),
+        Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset")),
+    ]);
+    let mut compressed = compress_stream(codec, input).unwrap();
+    let err = block_on(async {
+        loop {
+            match compressed.next().await {
+                Some(Ok(_)) => {}
+                Some(Err(err)) => break err,
+                None => panic!("stream ended without an error"),
+            }
+        }
+    });
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+}
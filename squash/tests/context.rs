@@ -0,0 +1,54 @@
+use squash::{Codec, Context};
+
+#[test]
+fn codecs_are_listed_with_their_plugins() {
+    let context = Context::get_default();
+    let codecs: Vec<Codec> = context.codecs().collect();
+    assert!(codecs.iter().any(|codec| codec.name() == "gzip"));
+
+    for plugin in context.plugins() {
+        assert_eq!(context.plugin(plugin.name()), Some(plugin));
+        for codec in plugin.codecs() {
+            assert!(codecs.contains(&codec));
+            assert_eq!(codec.plugin(), plugin);
+            assert_eq!(plugin.codec(codec.name()), Some(codec));
+        }
+    }
+}
+
+#[test]
+fn codecs_are_found_by_name_and_extension() {
+    let context = Context::get_default();
+    let gzip = context.codec("gzip").unwrap();
+    assert_eq!(Codec::find("gzip"), Some(gzip));
+    assert_eq!(context.codec_from_extension("gz"), Some(gzip));
+    assert_eq!(context.codec("no-such-codec"), None);
+    assert_eq!(context.plugin("no-such-plugin"), None);
+}
+
+#[test]
+fn plugins_report_their_licenses() {
+    let plugin = Context::get_default().codec("gzip").unwrap().plugin();
+    plugin.init().unwrap();
+    assert!(!plugin.licenses().is_empty());
+}
+
+#[test]
+fn every_plugin_is_diagnosed() {
+    let context = Context::get_default();
+    let report = context.diagnose();
+    assert_eq!(report.len(), context.plugins().count());
+
+    let gzip = context.codec("gzip").unwrap();
+    let diagnosis = report
+        .iter()
+        .find(|diagnosis| diagnosis.codecs.contains(&gzip))
+        .unwrap();
+    assert_eq!(diagnosis.plugin, gzip.plugin());
+    assert_eq!(diagnosis.name, gzip.plugin().name());
+    assert!(diagnosis.status.is_ok());
+    let directory = diagnosis.directory.as_ref().unwrap();
+    assert!(directory.join("squash.ini").is_file());
+    assert!(!diagnosis.licenses.is_empty());
+    assert!(diagnosis.to_string().starts_with(diagnosis.name));
+}
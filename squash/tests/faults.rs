@@ -0,0 +1,47 @@
+use squash::sys::SquashStatus;
+use squash::testing::{check_allocation_failures, use_canary_allocator, with_faults, Fault};
+use squash::{Codec, Stream, StreamType};
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+// A single test, as the allocator can only be installed once per process
+#[test]
+fn fault_injection() {
+    unsafe { use_canary_allocator().unwrap() };
+    let codec = Codec::find("gzip").unwrap();
+    check_allocation_failures(codec, LOREM_IPSUM);
+
+    // Constructors returning null report the failed allocation
+    let (result, stats) = with_faults(Fault::Nth(0), || {
+        Stream::new(codec, StreamType::Compress, None).map(drop)
+    });
+    assert_eq!(stats.injected, 1);
+    assert_eq!(result.unwrap_err().status(), SquashStatus::SQUASH_MEMORY);
+
+    let always = Fault::Random {
+        probability: 1.0,
+        seed: 42,
+    };
+    let (result, stats) = with_faults(always, || codec.compress(LOREM_IPSUM, None));
+    let err = result.unwrap_err();
+    assert_eq!(err.status(), SquashStatus::SQUASH_MEMORY);
+    assert_eq!(err.memory_limit_exceeded(), None);
+    assert!(stats.injected > 0);
+    assert_eq!(stats.injected, stats.allocations);
+
+    let never = Fault::Random {
+        probability: 0.0,
+        seed: 42,
+    };
+    let (result, stats) = with_faults(never, || codec.compress(LOREM_IPSUM, None));
+    assert!(result.is_ok());
+    assert_eq!(stats.injected, 0);
+
+    // The same seed fails the same allocations
+    let sometimes = Fault::Random {
+        probability: 0.5,
+        seed: 7,
+    };
+    let run = || with_faults(sometimes, || codec.compress(LOREM_IPSUM, None).is_ok());
+    assert_eq!(run(), run());
+}
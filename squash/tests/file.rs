@@ -0,0 +1,145 @@
+use squash::{Codec, CompressedFile};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process;
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("squash-{}-{}", process::id(), name))
+}
+
+#[test]
+fn write_then_read() {
+    let codec = Codec::find("gzip").unwrap();
+    let path = temp_path("write-then-read.gz");
+
+    let mut file = CompressedFile::open(&path, "wb", codec, None).unwrap();
+    file.write_all(LOREM_IPSUM).unwrap();
+    file.close().unwrap();
+
+    let compressed = fs::read(&path).unwrap();
+    assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+
+    let mut file = CompressedFile::open(&path, "rb", codec, None).unwrap();
+    assert!(!file.eof());
+    let mut decompressed = Vec::new();
+    file.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, LOREM_IPSUM);
+    assert!(file.eof());
+    assert!(file.error().is_none());
+    drop(file);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn open_missing() {
+    let codec = Codec::find("gzip").unwrap();
+    let err = CompressedFile::open(temp_path("missing.gz"), "rb", codec, None).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn read_corrupt() {
+    let codec = Codec::find("gzip").unwrap();
+    let path = temp_path("corrupt.gz");
+    fs::write(&path, b"this is not gzip data").unwrap();
+
+    let mut file = CompressedFile::open(&path, "rb", codec, None).unwrap();
+    let mut decompressed = Vec::new();
+    assert!(file.read_to_end(&mut decompressed).is_err());
+    assert!(file.error().is_some());
+    drop(file);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn from_file_into_inner() {
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom};
+
+    let codec = Codec::find("gzip").unwrap();
+    let path = temp_path("from-file.gz");
+    let mut raw = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    raw.write_all(b"header").unwrap();
+
+    let mut file = CompressedFile::from_file(raw, codec, None).unwrap();
+    file.write_all(LOREM_IPSUM).unwrap();
+    let mut raw = file.into_inner().unwrap();
+    raw.write_all(b"trailer").unwrap();
+
+    raw.seek(SeekFrom::Start(0)).unwrap();
+    let mut contents = Vec::new();
+    raw.read_to_end(&mut contents).unwrap();
+    assert!(contents.starts_with(b"header"));
+    assert!(contents.ends_with(b"trailer"));
+    let compressed = &contents[b"header".len()..contents.len() - b"trailer".len()];
+    assert_eq!(codec.decompress(compressed, None).unwrap(), LOREM_IPSUM);
+
+    raw.seek(SeekFrom::Start(b"header".len() as u64)).unwrap();
+    let mut file = CompressedFile::from_file(raw, codec, None).unwrap();
+    let mut decompressed = vec![0; LOREM_IPSUM.len()];
+    file.read_exact(&mut decompressed).unwrap();
+    assert_eq!(decompressed, LOREM_IPSUM);
+    drop(file);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn shared_between_threads() {
+    use std::sync::Arc;
+    use std::thread;
+
+    const THREADS: usize = 4;
+    const LINES: usize = 100;
+
+    let codec = Codec::find("gzip").unwrap();
+    let path = temp_path("shared.gz");
+    let file = Arc::new(CompressedFile::open(&path, "wb", codec, None).unwrap());
+
+    let threads: Vec<_> = (0..THREADS)
+        .map(|n| {
+            let file = Arc::clone(&file);
+            thread::spawn(move || {
+                for _ in 0..LINES {
+                    // Each line is written in pieces, which must not be interleaved
+                    let mut guard = file.lock();
+                    write!(guard, "thread {}: ", n).unwrap();
+                    guard.write_all(&LOREM_IPSUM[..32]).unwrap();
+                    guard.write_all(b"\n").unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    Arc::try_unwrap(file).unwrap().close().unwrap();
+
+    let mut file = CompressedFile::open(&path, "rb", codec, None).unwrap();
+    let mut decompressed = Vec::new();
+    file.read_to_end(&mut decompressed).unwrap();
+    let lines: Vec<_> = decompressed
+        .split(|&b| b == b'\n')
+        .filter(|l| !l.is_empty())
+        .collect();
+    assert_eq!(lines.len(), THREADS * LINES);
+    for line in lines {
+        assert!(line.starts_with(b"thread "));
+        assert!(line.ends_with(&LOREM_IPSUM[..32]));
+    }
+    drop(file);
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,74 @@
+use squash::read::Decoder;
+use squash::write::Encoder;
+use squash::{Codec, Status, Stream, StreamType};
+use std::io::{self, Read, Write};
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+#[test]
+fn flush_pushes_out_written_data() {
+    let codec = Codec::find("gzip").unwrap();
+    assert!(codec.can_flush());
+
+    let mut encoder = Encoder::new(codec, Vec::new()).unwrap();
+    encoder.write_all(LOREM_IPSUM).unwrap();
+    encoder.flush().unwrap();
+
+    // Everything written so far must be decodable without finishing the stream
+    let compressed = encoder.get_ref().clone();
+    let mut stream = Stream::new(codec, StreamType::Decompress, None).unwrap();
+    let mut decompressed = vec![0; LOREM_IPSUM.len() * 2];
+    let progress = stream.process(&compressed, &mut decompressed).unwrap();
+    assert_eq!(progress.status, Status::Ok);
+    assert_eq!(progress.read, compressed.len());
+    assert_eq!(&decompressed[..progress.written], LOREM_IPSUM);
+
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+}
+
+#[test]
+fn flush_unsupported() {
+    let codec = Codec::find("snappy").unwrap();
+    assert!(!codec.can_flush());
+
+    let mut encoder = Encoder::new(codec, Vec::new()).unwrap();
+    encoder.write_all(LOREM_IPSUM).unwrap();
+    let err = encoder.flush().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn flush_by_restarting() {
+    // Codecs which can flush use squash_stream_flush, the others restart: either way the output
+    // must decode as one stream
+    for name in &["gzip", "zstd", "bzip2", "xz"] {
+        let codec = match Codec::find(name) {
+            Some(codec) if codec.supports_concatenation() => codec,
+            _ => continue,
+        };
+
+        let mut encoder = Encoder::new(codec, Vec::new()).unwrap();
+        encoder.set_restart_on_flush(true);
+        encoder.write_all(LOREM_IPSUM).unwrap();
+        encoder.flush().unwrap();
+
+        let first_member = encoder.get_ref().clone();
+        if !codec.can_flush() {
+            // The first member is complete once flushed
+            assert_eq!(codec.decompress(&first_member, None).unwrap(), LOREM_IPSUM);
+
+            // Flushing again without new data doesn't start an empty member
+            encoder.flush().unwrap();
+            assert_eq!(encoder.get_ref().len(), first_member.len());
+        }
+
+        encoder.write_all(LOREM_IPSUM).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut decoder = Decoder::new(codec, &compressed[..]).unwrap();
+        assert!(decoder.multi_member());
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, [LOREM_IPSUM, LOREM_IPSUM].concat());
+    }
+}
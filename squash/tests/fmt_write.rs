@@ -0,0 +1,40 @@
+use squash::write::Encoder;
+use squash::{Codec, CompressedFile};
+use std::fmt::Write;
+use std::fs;
+use std::process;
+
+#[test]
+fn encoder() {
+    let codec = Codec::find("gzip").unwrap();
+    let mut encoder = Encoder::new(codec, Vec::new()).unwrap();
+    writeln!(encoder, "id,name,value").unwrap();
+    for i in 0..3 {
+        writeln!(encoder, "{},item {},{:.2}", i, i, i as f64 / 4.0).unwrap();
+    }
+    let compressed = encoder.finish().unwrap();
+
+    let decompressed = codec.decompress(&compressed, None).unwrap();
+    assert_eq!(
+        decompressed,
+        b"id,name,value\n0,item 0,0.00\n1,item 1,0.25\n2,item 2,0.50\n"
+    );
+}
+
+#[test]
+fn compressed_file() {
+    let codec = Codec::find("gzip").unwrap();
+    let path = std::env::temp_dir().join(format!("squash-{}-fmt-write.gz", process::id()));
+
+    let mut file = CompressedFile::open(&path, "wb", codec, None).unwrap();
+    writeln!(file, "first line").unwrap();
+    let line = 2;
+    write!(file.lock(), "line {}", line).unwrap();
+    file.close().unwrap();
+
+    let compressed = fs::read(&path).unwrap();
+    let decompressed = codec.decompress(&compressed, None).unwrap();
+    assert_eq!(decompressed, b"first line\nline 2");
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "futures-io")]
+
+use futures::executor::block_on;
+use futures::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use squash::futures_io::{Decoder, Encoder};
+use squash::Codec;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+#[test]
+fn round_trip_through_duplex() {
+    let codec = Codec::find("gzip").unwrap();
+    // A small pipe makes both sides wait on each other many times
+    let (writer, reader) = tokio::io::duplex(64);
+    let (writer, reader) = (writer.compat(), reader.compat());
+
+    let write = async move {
+        let mut encoder = Encoder::new(codec, writer).unwrap();
+        for chunk in LOREM_IPSUM.chunks(100) {
+            encoder.write_all(chunk).await.unwrap();
+        }
+        encoder.close().await.unwrap();
+    };
+    let read = async move {
+        let mut decoder = Decoder::new(codec, BufReader::with_capacity(16, reader)).unwrap();
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).await.unwrap();
+        decompressed
+    };
+
+    let ((), decompressed) = block_on(futures::future::join(write, read));
+    assert_eq!(decompressed, LOREM_IPSUM);
+}
+
+#[test]
+fn concatenated_members() {
+    let codec = Codec::find("gzip").unwrap();
+    let (first, second) = LOREM_IPSUM.split_at(LOREM_IPSUM.len() / 2);
+    let mut compressed = codec.compress(first, None).unwrap();
+    compressed.extend(codec.compress(second, None).unwrap());
+
+    let mut decoder = Decoder::new(codec, &compressed[..]).unwrap();
+    let mut decompressed = Vec::new();
+    block_on(decoder.read_to_end(&mut decompressed)).unwrap();
+    assert_eq!(decompressed, LOREM_IPSUM);
+}
@@ -0,0 +1,49 @@
+use squash::read::Decoder;
+use squash::sys::{squash_free, squash_malloc};
+use squash::testing::{
+    assert_no_leaks, canary_failures, outstanding_allocations, use_canary_allocator, warm_up,
+};
+use squash::write::Encoder;
+use squash::{Codec, Options, Stream, StreamType};
+use std::io::{Read, Write};
+use std::panic;
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+// A single test, as the allocator can only be installed once per process
+#[test]
+fn leak_checking() {
+    unsafe { use_canary_allocator().unwrap() };
+    let codec = Codec::find("gzip").unwrap();
+    warm_up(codec);
+
+    assert_no_leaks(|| {
+        let mut options = Options::new(codec).unwrap();
+        options.set("level", "9").unwrap();
+        let mut encoder = Encoder::with_options(codec, Vec::new(), Some(&options)).unwrap();
+        encoder.write_all(LOREM_IPSUM).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = Decoder::new(codec, &compressed[..]).unwrap();
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, LOREM_IPSUM);
+    });
+
+    // A stream which is never dropped is reported
+    let leak = panic::catch_unwind(|| {
+        assert_no_leaks(|| std::mem::forget(Stream::new(codec, StreamType::Compress, None)))
+    });
+    assert!(leak.is_err());
+
+    let before = outstanding_allocations();
+    let ptr = unsafe { squash_malloc(100) };
+    assert!(outstanding_allocations() > before);
+    unsafe { squash_free(ptr) };
+
+    // Freeing a pointer squash didn't allocate is caught instead of corrupting the heap
+    let failures = canary_failures();
+    let mut block = [0u64; 8];
+    unsafe { squash_free(block.as_mut_ptr().add(4) as *mut _) };
+    assert_eq!(canary_failures(), failures + 1);
+}
@@ -0,0 +1,40 @@
+use squash::alloc::{measure, use_tracking_allocator, MemoryStats};
+use squash::sys::{squash_free, squash_malloc, squash_realloc};
+use squash::Codec;
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+// A single test, as the allocator can only be installed once per process
+#[test]
+fn measure_squash_allocations() {
+    unsafe { use_tracking_allocator().unwrap() };
+
+    let ((), stats) = measure(|| unsafe {
+        let ptr = squash_malloc(1000);
+        let ptr = squash_realloc(ptr, 3000);
+        let ((), inner) = measure(|| squash_free(squash_malloc(500)));
+        assert_eq!(inner.peak, 500);
+        assert_eq!(inner.live, 0);
+        squash_free(ptr);
+    });
+    assert_eq!(
+        stats,
+        MemoryStats {
+            live: 0,
+            peak: 3500,
+            allocated: 4500,
+            allocations: 2,
+            reallocations: 1,
+            deallocations: 2,
+        }
+    );
+
+    // Load the plugin first, so only compression itself is measured
+    let codec = Codec::find("gzip").unwrap();
+    codec.compress(LOREM_IPSUM, None).unwrap();
+    let (compressed, stats) = measure(|| codec.compress(LOREM_IPSUM, None).unwrap());
+    assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+    assert!(stats.peak > 0);
+    assert!(stats.allocations > 0);
+    assert_eq!(stats.live, 0);
+}
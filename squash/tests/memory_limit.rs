@@ -0,0 +1,68 @@
+use squash::alloc::{
+    memory_in_use, memory_limit, set_memory_limit, use_rust_allocator, with_memory_limit,
+};
+use squash::read::Decoder;
+use squash::sys::{squash_free, squash_malloc, SquashStatus};
+use squash::Codec;
+use std::io;
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+// A single test, as the allocator can only be installed once per process
+#[test]
+fn memory_limits() {
+    unsafe { use_rust_allocator().unwrap() };
+
+    // Load the plugin outside of any limit
+    let codec = Codec::find("gzip").unwrap();
+    let compressed = codec.compress(LOREM_IPSUM, None).unwrap();
+
+    let err = with_memory_limit(1024, || codec.compress(LOREM_IPSUM, None)).unwrap_err();
+    assert_eq!(err.status(), SquashStatus::SQUASH_MEMORY);
+    let exceeded = err.memory_limit_exceeded().unwrap();
+    assert_eq!(exceeded.limit, 1024);
+    assert!(exceeded.requested > 0);
+
+    // The error survives conversion into an io::Error
+    let err = io::Error::from(err);
+    assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+    let inner = err.get_ref().unwrap().downcast_ref::<squash::Error>();
+    assert_eq!(inner.unwrap().memory_limit_exceeded(), Some(exceeded));
+
+    // Limits also apply to creating streams, which squash reports by returning null
+    let err = with_memory_limit(16, || Decoder::new(codec, &compressed[..]))
+        .err()
+        .unwrap();
+    let inner = err
+        .get_ref()
+        .unwrap()
+        .downcast_ref::<squash::Error>()
+        .unwrap();
+    assert_eq!(inner.status(), SquashStatus::SQUASH_MEMORY);
+    assert_eq!(inner.memory_limit_exceeded().unwrap().limit, 16);
+    // The refusal was picked up, and isn't blamed on later errors
+    let err = codec.decompress(b"not gzip", None).unwrap_err();
+    assert_eq!(err.memory_limit_exceeded(), None);
+
+    // A generous limit lets the operation through
+    let decompressed = with_memory_limit(64 << 20, || codec.decompress(&compressed, None));
+    assert_eq!(decompressed.unwrap(), LOREM_IPSUM);
+
+    // Nested limits can't exceed the outer one
+    with_memory_limit(1000, || unsafe {
+        let ptr = squash_malloc(600);
+        assert!(!ptr.is_null());
+        with_memory_limit(1000, || assert!(squash_malloc(500).is_null()));
+        squash_free(ptr);
+    });
+
+    let limit = memory_in_use() + 100;
+    set_memory_limit(Some(limit));
+    assert_eq!(memory_limit(), Some(limit));
+    assert!(unsafe { squash_malloc(1000) }.is_null());
+    let err = codec.compress(LOREM_IPSUM, None).unwrap_err();
+    assert_eq!(err.memory_limit_exceeded().unwrap().limit, limit);
+    set_memory_limit(None);
+    assert_eq!(memory_limit(), None);
+    assert!(codec.compress(LOREM_IPSUM, None).is_ok());
+}
@@ -0,0 +1,76 @@
+use squash::read::Decoder;
+use squash::Codec;
+use std::io::Read;
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+macro_rules! test_concatenated {
+    ($codec_ident:ident) => {
+        mod $codec_ident {
+            #[test]
+            fn decode_all_members() {
+                super::decode_all_members(stringify!($codec_ident));
+            }
+
+            #[test]
+            fn decode_first_member() {
+                super::decode_first_member(stringify!($codec_ident));
+            }
+        }
+    };
+}
+
+test_concatenated! { bzip2 }
+test_concatenated! { gzip }
+test_concatenated! { xz }
+test_concatenated! { zstd }
+
+fn concatenated(codec: Codec) -> Vec<u8> {
+    let mut compressed = codec.compress(LOREM_IPSUM, None).unwrap();
+    let second = codec.compress(LOREM_IPSUM, None).unwrap();
+    compressed.extend_from_slice(&second);
+    compressed
+}
+
+fn decode_all_members(codec_name: &str) {
+    let codec = Codec::find(codec_name).unwrap();
+    assert!(codec.supports_concatenation());
+    let compressed = concatenated(codec);
+
+    let mut decoder = Decoder::new(codec, &compressed[..]).unwrap();
+    assert!(decoder.multi_member());
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).unwrap();
+
+    assert_eq!(decompressed.len(), LOREM_IPSUM.len() * 2);
+    assert_eq!(&decompressed[..LOREM_IPSUM.len()], LOREM_IPSUM);
+    assert_eq!(&decompressed[LOREM_IPSUM.len()..], LOREM_IPSUM);
+}
+
+fn decode_first_member(codec_name: &str) {
+    let codec = Codec::find(codec_name).unwrap();
+    let compressed = concatenated(codec);
+
+    let mut decoder = Decoder::new(codec, &compressed[..]).unwrap();
+    decoder.set_multi_member(false);
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, LOREM_IPSUM);
+
+    let remaining = decoder.into_inner();
+    assert_eq!(remaining, &compressed[compressed.len() - remaining.len()..]);
+    assert!(!remaining.is_empty());
+}
+
+#[test]
+fn single_member_codec() {
+    let codec = Codec::find("snappy").unwrap();
+    assert!(!codec.supports_concatenation());
+
+    let compressed = codec.compress(LOREM_IPSUM, None).unwrap();
+    let mut decoder = Decoder::new(codec, &compressed[..]).unwrap();
+    assert!(!decoder.multi_member());
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, LOREM_IPSUM);
+}
@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+
+use squash::Codec;
+
+fn from_path(path: &str) -> Option<(&'static str, PathBuf)> {
+    Codec::from_path(Path::new(path)).map(|(codec, output)| (codec.name(), output))
+}
+
+#[test]
+fn codecs_are_found_from_paths() {
+    assert_eq!(from_path("logs.gz"), Some(("gzip", "logs".into())));
+    assert_eq!(
+        from_path("dir/logs.tar.zst"),
+        Some(("zstd", "dir/logs.tar".into()))
+    );
+    assert_eq!(from_path("LOGS.TAR.GZ"), Some(("gzip", "LOGS.TAR".into())));
+    assert_eq!(from_path("logs.tgz"), Some(("gzip", "logs.tar".into())));
+    assert_eq!(from_path("logs.TBZ2"), Some(("bzip2", "logs.tar".into())));
+    assert_eq!(from_path("logs.txz"), Some(("xz", "logs.tar".into())));
+    assert_eq!(from_path("Données.TXZ"), Some(("xz", "Données.tar".into())));
+    assert_eq!(
+        from_path("ÉTÉ/Journal.Tar.Zst"),
+        Some(("zstd", "ÉTÉ/Journal.Tar".into()))
+    );
+    assert_ne!(from_path("logs.taZ").map(|(codec, _)| codec), Some("gzip"));
+    if Codec::find("compress").is_some() {
+        assert_eq!(from_path("logs.Z"), Some(("compress", "logs".into())));
+    }
+    assert_eq!(from_path("logs.txt"), None);
+    assert_eq!(from_path("logs"), None);
+}
+
+#[test]
+fn output_paths_append_the_extension() {
+    let gzip = Codec::find("gzip").unwrap();
+    assert_eq!(
+        gzip.output_path(Path::new("dir/logs.tar")),
+        Some(PathBuf::from("dir/logs.tar.gz"))
+    );
+    let (codec, output) = Codec::from_path(Path::new("logs.tar.gz")).unwrap();
+    assert_eq!(codec.output_path(&output), Some("logs.tar.gz".into()));
+}
@@ -0,0 +1,112 @@
+use squash::sys::SquashStreamState;
+use squash::{Codec, Options, Status, StreamPool, StreamType};
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+fn compress_with(pool: &StreamPool, codec: Codec, options: Option<&Options>) -> Vec<u8> {
+    let mut stream = pool.get(codec, StreamType::Compress, options).unwrap();
+    assert_eq!(stream.state(), SquashStreamState::SQUASH_STREAM_STATE_IDLE);
+    let mut output = vec![0; codec.max_compressed_size(LOREM_IPSUM.len())];
+    let mut len = stream.process(LOREM_IPSUM, &mut output).unwrap().written;
+    loop {
+        let progress = stream.finish(&[], &mut output[len..]).unwrap();
+        len += progress.written;
+        if progress.status != Status::Processing {
+            break;
+        }
+    }
+    output.truncate(len);
+    output
+}
+
+#[test]
+fn finished_streams_are_recycled() {
+    let codec = Codec::find("gzip").unwrap();
+    let pool = StreamPool::new();
+    for _ in 0..3 {
+        let compressed = compress_with(&pool, codec, None);
+        assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+    }
+    // Rebuilt streams were created for the requests all the same
+    let stats = pool.stats();
+    assert_eq!(stats.misses, 3);
+    assert_eq!(stats.hits, 0);
+    assert_eq!(stats.rebuilt, 3);
+    assert_eq!(stats.hit_rate(), 0.0);
+    assert_eq!(pool.idle(), 1);
+}
+
+#[test]
+fn unused_streams_are_hits() {
+    let codec = Codec::find("gzip").unwrap();
+    let pool = StreamPool::new();
+    for _ in 0..3 {
+        drop(pool.get(codec, StreamType::Decompress, None).unwrap());
+    }
+    let stats = pool.stats();
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.hits, 2);
+    assert!((stats.hit_rate() - 2.0 / 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn keyed_by_options() {
+    let codec = Codec::find("gzip").unwrap();
+    let mut options = Options::new(codec).unwrap();
+    options.set("level", "1").unwrap();
+    let pool = StreamPool::new();
+
+    compress_with(&pool, codec, None);
+    compress_with(&pool, codec, Some(&options));
+    drop(pool.get(codec, StreamType::Decompress, None).unwrap());
+    assert_eq!(pool.stats().misses, 3);
+    assert_eq!(pool.idle(), 3);
+
+    let stream = pool
+        .get(codec, StreamType::Compress, Some(&options))
+        .unwrap();
+    assert_eq!(stream.options().unwrap().as_ptr(), options.as_ptr());
+    assert_eq!(pool.idle(), 2);
+}
+
+#[test]
+fn least_recently_used_options_are_evicted() {
+    let codec = Codec::find("gzip").unwrap();
+    let pool = StreamPool::new();
+    for _ in 0..100 {
+        let options = Options::new(codec).unwrap();
+        drop(
+            pool.get(codec, StreamType::Compress, Some(&options))
+                .unwrap(),
+        );
+    }
+    assert_eq!(pool.idle(), 64);
+    assert_eq!(pool.stats().discarded, 36);
+}
+
+#[test]
+fn abandoned_streams_are_discarded() {
+    let codec = Codec::find("gzip").unwrap();
+    let pool = StreamPool::new();
+    let mut stream = pool.get(codec, StreamType::Compress, None).unwrap();
+    let mut output = vec![0; 1024];
+    stream.process(LOREM_IPSUM, &mut output).unwrap();
+    drop(stream);
+    assert_eq!(pool.stats().discarded, 1);
+    assert_eq!(pool.idle(), 0);
+}
+
+#[test]
+fn max_idle() {
+    let codec = Codec::find("gzip").unwrap();
+    let pool = StreamPool::with_max_idle(1);
+    let first = pool.get(codec, StreamType::Compress, None).unwrap();
+    let second = pool.get(codec, StreamType::Compress, None).unwrap();
+    drop(first);
+    drop(second);
+    assert_eq!(pool.idle(), 1);
+    assert_eq!(pool.stats().discarded, 1);
+
+    pool.clear();
+    assert_eq!(pool.idle(), 0);
+}
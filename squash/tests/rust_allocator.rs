@@ -0,0 +1,53 @@
+use squash::alloc::{use_rust_allocator, AlreadyInstalled};
+use squash::sys::{squash_aligned_alloc, squash_aligned_free, squash_free, squash_malloc};
+use squash::Codec;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+/// Remembers the largest allocation and deallocation it has seen
+struct Recording;
+
+static LARGEST_ALLOC: AtomicUsize = AtomicUsize::new(0);
+static LARGEST_DEALLOC: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Recording {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LARGEST_ALLOC.fetch_max(layout.size(), Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LARGEST_DEALLOC.fetch_max(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Recording = Recording;
+
+// A single test, as the allocator can only be installed once per process
+#[test]
+fn squash_allocates_through_global_allocator() {
+    unsafe {
+        use_rust_allocator().unwrap();
+        assert_eq!(use_rust_allocator(), Err(AlreadyInstalled));
+
+        const SIZE: usize = 64 << 20;
+        let ptr = squash_malloc(SIZE);
+        assert!(!ptr.is_null());
+        assert!(LARGEST_ALLOC.load(Ordering::SeqCst) >= SIZE);
+        squash_free(ptr);
+        assert!(LARGEST_DEALLOC.load(Ordering::SeqCst) >= SIZE);
+
+        let ptr = squash_aligned_alloc(4096, 100);
+        assert!(!ptr.is_null());
+        assert_eq!(ptr as usize % 4096, 0);
+        squash_aligned_free(ptr);
+    }
+
+    let codec = Codec::find("gzip").unwrap();
+    let compressed = codec.compress(LOREM_IPSUM, None).unwrap();
+    assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+}
@@ -0,0 +1,37 @@
+use std::env;
+
+use squash::{Codec, Context, SearchPathError};
+
+// The search path can only be set before the default context is created, which happens once per
+// process, so everything is checked in a single test.
+#[test]
+fn search_path_is_set_before_the_default_context_is_created() {
+    let missing = env::temp_dir().join("squash-no-such-plugin-directory");
+    let extra = env::temp_dir();
+
+    match unsafe { Context::set_search_path([&missing]) } {
+        Err(SearchPathError::NotFound(directory)) => assert_eq!(directory, missing),
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let plugins = env::var_os("SQUASH_PLUGINS");
+    unsafe { Context::set_search_path([&extra]).unwrap() };
+    match plugins {
+        Some(plugins) => {
+            let merged: Vec<_> =
+                env::split_paths(&env::var_os("SQUASH_PLUGINS").unwrap()).collect();
+            let mut expected: Vec<_> = env::split_paths(&plugins).collect();
+            expected.push(extra.clone());
+            assert_eq!(merged, expected);
+        }
+        // The environment is only changed when squash would read it
+        None => assert_eq!(env::var_os("SQUASH_PLUGINS"), None),
+    }
+
+    // The plugins found before are still found
+    assert!(Codec::find("gzip").is_some());
+    assert!(matches!(
+        unsafe { Context::set_search_path([&extra]) },
+        Err(SearchPathError::ContextCreated)
+    ));
+}
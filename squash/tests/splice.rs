@@ -0,0 +1,151 @@
+use squash::{Codec, StreamType};
+use std::io::{self, Read};
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+fn splice_vec(
+    codec: Codec,
+    stream_type: StreamType,
+    input: &[u8],
+    limit: Option<usize>,
+) -> Vec<u8> {
+    let mut input = input;
+    let mut output = Vec::new();
+    codec
+        .splice(
+            stream_type,
+            |buf| input.read(buf),
+            |data| {
+                output.extend_from_slice(data);
+                Ok(())
+            },
+            limit,
+            None,
+        )
+        .unwrap();
+    output
+}
+
+#[test]
+fn round_trip() {
+    let codec = Codec::find("gzip").unwrap();
+    let compressed = splice_vec(codec, StreamType::Compress, LOREM_IPSUM, None);
+    assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+
+    let decompressed = splice_vec(codec, StreamType::Decompress, &compressed, None);
+    assert_eq!(decompressed, LOREM_IPSUM);
+}
+
+#[test]
+fn limit() {
+    let codec = Codec::find("gzip").unwrap();
+    let compressed = splice_vec(codec, StreamType::Compress, LOREM_IPSUM, Some(100));
+    assert_eq!(
+        codec.decompress(&compressed, None).unwrap(),
+        &LOREM_IPSUM[..100]
+    );
+
+    let compressed = codec.compress(LOREM_IPSUM, None).unwrap();
+    let decompressed = splice_vec(codec, StreamType::Decompress, &compressed, Some(100));
+    assert_eq!(decompressed, &LOREM_IPSUM[..100]);
+}
+
+#[test]
+fn zero_limit() {
+    let codec = Codec::find("gzip").unwrap();
+    let compressed = splice_vec(codec, StreamType::Compress, LOREM_IPSUM, Some(0));
+    assert!(codec.decompress(&compressed, None).unwrap().is_empty());
+
+    let compressed = codec.compress(LOREM_IPSUM, None).unwrap();
+    let decompressed = splice_vec(codec, StreamType::Decompress, &compressed, Some(0));
+    assert!(decompressed.is_empty());
+}
+
+#[test]
+fn reader_error() {
+    let codec = Codec::find("gzip").unwrap();
+    let err = codec
+        .splice(
+            StreamType::Compress,
+            |_| {
+                Err(io::Error::new(
+                    io::ErrorKind::ConnectionReset,
+                    "reader failed",
+                ))
+            },
+            |_| Ok(()),
+            None,
+            None,
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+    assert_eq!(err.to_string(), "reader failed");
+}
+
+#[test]
+fn writer_error() {
+    let codec = Codec::find("gzip").unwrap();
+    let mut input = LOREM_IPSUM;
+    let err = codec
+        .splice(
+            StreamType::Compress,
+            |buf| input.read(buf),
+            |_| Err(io::Error::new(io::ErrorKind::BrokenPipe, "writer failed")),
+            None,
+            None,
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+#[should_panic(expected = "panic in reader")]
+fn reader_panic() {
+    let codec = Codec::find("gzip").unwrap();
+    let _ = codec.splice(
+        StreamType::Compress,
+        |_| panic!("panic in reader"),
+        |_| Ok(()),
+        None,
+        None,
+    );
+}
+
+#[test]
+fn copy_round_trip() {
+    let codec = Codec::find("gzip").unwrap();
+
+    let mut compressed = Vec::new();
+    let copied =
+        squash::copy_compress(codec, &mut &LOREM_IPSUM[..], &mut compressed, None).unwrap();
+    assert_eq!(copied.read, LOREM_IPSUM.len() as u64);
+    assert_eq!(copied.written, compressed.len() as u64);
+
+    let mut decompressed = Vec::new();
+    let copied =
+        squash::copy_decompress(codec, &mut &compressed[..], &mut decompressed, None).unwrap();
+    assert_eq!(copied.read, compressed.len() as u64);
+    assert_eq!(copied.written, LOREM_IPSUM.len() as u64);
+    assert_eq!(decompressed, LOREM_IPSUM);
+}
+
+#[test]
+fn copy_limit() {
+    let codec = Codec::find("gzip").unwrap();
+
+    let mut compressed = Vec::new();
+    let copied = squash::copy(
+        codec,
+        StreamType::Compress,
+        &mut &LOREM_IPSUM[..],
+        &mut compressed,
+        Some(100),
+        None,
+    )
+    .unwrap();
+    assert!(copied.read >= 100);
+    assert_eq!(
+        codec.decompress(&compressed, None).unwrap(),
+        &LOREM_IPSUM[..100]
+    );
+}
@@ -0,0 +1,74 @@
+#![cfg(unix)]
+
+use squash::{Codec, StreamType};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process;
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+fn temp_file(name: &str) -> (PathBuf, File) {
+    let path = std::env::temp_dir().join(format!("squash-{}-{}", process::id(), name));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    (path, file)
+}
+
+#[test]
+fn round_trip() {
+    let codec = Codec::find("gzip").unwrap();
+    let (input_path, mut input) = temp_file("round-trip-input");
+    let (compressed_path, mut compressed) = temp_file("round-trip-compressed");
+    let (output_path, mut output) = temp_file("round-trip-output");
+
+    input.write_all(b"header").unwrap();
+    input.write_all(LOREM_IPSUM).unwrap();
+    input.seek(SeekFrom::Start(6)).unwrap();
+
+    squash::splice_files(codec, StreamType::Compress, &input, &compressed, None).unwrap();
+    assert_eq!(
+        input.stream_position().unwrap(),
+        6 + LOREM_IPSUM.len() as u64
+    );
+    let compressed_len = compressed.stream_position().unwrap();
+    assert_eq!(compressed_len, compressed.metadata().unwrap().len());
+
+    compressed.seek(SeekFrom::Start(0)).unwrap();
+    squash::splice_files(codec, StreamType::Decompress, &compressed, &output, None).unwrap();
+    assert_eq!(compressed.stream_position().unwrap(), compressed_len);
+    assert_eq!(output.stream_position().unwrap(), LOREM_IPSUM.len() as u64);
+
+    let mut decompressed = Vec::new();
+    output.seek(SeekFrom::Start(0)).unwrap();
+    output.read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, LOREM_IPSUM);
+
+    for path in &[input_path, compressed_path, output_path] {
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn error_restores_positions() {
+    let codec = Codec::find("gzip").unwrap();
+    let (input_path, mut input) = temp_file("error-input");
+    let (output_path, mut output) = temp_file("error-output");
+
+    input.write_all(b"this is not gzip data").unwrap();
+    input.seek(SeekFrom::Start(4)).unwrap();
+    output.write_all(b"existing").unwrap();
+
+    assert!(squash::splice_files(codec, StreamType::Decompress, &input, &output, None).is_err());
+    assert_eq!(input.stream_position().unwrap(), 4);
+    assert_eq!(output.stream_position().unwrap(), 8);
+
+    for path in &[input_path, output_path] {
+        fs::remove_file(path).unwrap();
+    }
+}
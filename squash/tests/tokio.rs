@@ -0,0 +1,53 @@
+#![cfg(feature = "tokio")]
+
+use squash::tokio::{Decoder, Encoder};
+use squash::Codec;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+#[tokio::test]
+async fn round_trip_through_duplex() {
+    let codec = Codec::find("gzip").unwrap();
+    // A small pipe makes both sides wait on each other many times
+    let (writer, reader) = tokio::io::duplex(64);
+
+    let write = async move {
+        let mut encoder = Encoder::new(codec, writer).unwrap();
+        for chunk in LOREM_IPSUM.chunks(100) {
+            encoder.write_all(chunk).await.unwrap();
+        }
+        encoder.shutdown().await.unwrap();
+    };
+    let read = async move {
+        let mut decoder = Decoder::new(codec, BufReader::with_capacity(16, reader)).unwrap();
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).await.unwrap();
+        decompressed
+    };
+
+    let ((), decompressed) = tokio::join!(write, read);
+    assert_eq!(decompressed, LOREM_IPSUM);
+}
+
+#[tokio::test]
+async fn flush_then_continue() {
+    let codec = Codec::find("gzip").unwrap();
+    let mut encoder = Encoder::new(codec, Vec::new()).unwrap();
+    let (first, second) = LOREM_IPSUM.split_at(LOREM_IPSUM.len() / 2);
+    encoder.write_all(first).await.unwrap();
+    encoder.flush().await.unwrap();
+    encoder.write_all(second).await.unwrap();
+    encoder.shutdown().await.unwrap();
+
+    let compressed = encoder.into_inner();
+    assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+}
+
+#[tokio::test]
+async fn write_after_shutdown() {
+    let codec = Codec::find("gzip").unwrap();
+    let mut encoder = Encoder::new(codec, Vec::new()).unwrap();
+    encoder.shutdown().await.unwrap();
+    assert!(encoder.write_all(LOREM_IPSUM).await.is_err());
+}
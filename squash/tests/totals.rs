@@ -0,0 +1,60 @@
+use squash::read::Decoder;
+use squash::write::Encoder;
+use squash::{Codec, StreamType, Totals};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+#[test]
+fn encoder_totals() {
+    let codec = Codec::find("gzip").unwrap();
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let mut encoder = Encoder::new(codec, Vec::new()).unwrap();
+    {
+        let reports = Arc::clone(&reports);
+        encoder.set_progress_callback(1000, move |totals| reports.lock().unwrap().push(*totals));
+    }
+    for chunk in LOREM_IPSUM.chunks(100) {
+        encoder.write_all(chunk).unwrap();
+    }
+    encoder.try_finish().unwrap();
+
+    assert_eq!(encoder.total_in(), LOREM_IPSUM.len() as u64);
+    assert_eq!(encoder.total_out(), encoder.get_ref().len() as u64);
+    assert!(encoder.ratio() > 1.0);
+
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len() as u64, encoder.total_in() / 1000);
+    for (i, report) in reports.iter().enumerate() {
+        assert_eq!(report.stream_type, StreamType::Compress);
+        assert!(report.total_in >= (i as u64 + 1) * 1000);
+    }
+}
+
+#[test]
+fn decoder_totals_span_members() {
+    let codec = Codec::find("gzip").unwrap();
+    let mut compressed = codec.compress(LOREM_IPSUM, None).unwrap();
+    compressed.extend(codec.compress(LOREM_IPSUM, None).unwrap());
+
+    let mut decoder = Decoder::new(codec, &compressed[..]).unwrap();
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).unwrap();
+
+    assert_eq!(decoder.total_in(), compressed.len() as u64);
+    assert_eq!(decoder.total_out(), decompressed.len() as u64);
+    let expected = decompressed.len() as f64 / compressed.len() as f64;
+    assert!((decoder.ratio() - expected).abs() < 1e-9);
+}
+
+#[test]
+fn ratio_without_output() {
+    let totals = Totals {
+        stream_type: StreamType::Compress,
+        total_in: 100,
+        total_out: 0,
+    };
+    assert_eq!(totals.uncompressed(), 100);
+    assert_eq!(totals.ratio(), 0.0);
+}
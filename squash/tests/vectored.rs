@@ -0,0 +1,44 @@
+use squash::write::Encoder;
+use squash::Codec;
+use std::io::{IoSlice, Write};
+
+const LOREM_IPSUM: &[u8] = include_bytes!("../../tests/data/lorem.txt");
+
+fn records() -> Vec<IoSlice<'static>> {
+    let (header, payload) = LOREM_IPSUM.split_at(16);
+    let (payload, rest) = payload.split_at(1000);
+    vec![
+        IoSlice::new(header),
+        IoSlice::new(&[]),
+        IoSlice::new(payload),
+        IoSlice::new(rest),
+    ]
+}
+
+#[test]
+fn encoder_write_vectored() {
+    let codec = Codec::find("gzip").unwrap();
+    let mut encoder = Encoder::new(codec, Vec::new()).unwrap();
+    let written = encoder.write_vectored(&records()).unwrap();
+    assert_eq!(written, LOREM_IPSUM.len());
+
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+}
+
+#[test]
+fn compress_vectored() {
+    // A streaming codec, and one which only compresses whole buffers
+    for name in &["gzip", "snappy"] {
+        let codec = Codec::find(name).unwrap();
+        let compressed = codec.compress_vectored(&records(), None).unwrap();
+        assert_eq!(codec.decompress(&compressed, None).unwrap(), LOREM_IPSUM);
+    }
+}
+
+#[test]
+fn compress_vectored_empty() {
+    let codec = Codec::find("gzip").unwrap();
+    let compressed = codec.compress_vectored(&[], None).unwrap();
+    assert!(codec.decompress(&compressed, None).unwrap().is_empty());
+}